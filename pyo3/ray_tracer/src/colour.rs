@@ -41,6 +41,10 @@ impl Colour {
         return format!("{} {} {}", ir, ig, ib);
     }
 
+    pub fn luminance(self) -> f64 {
+        return 0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b;
+    }
+
     pub fn interpolate(self, end: Colour, t: f64) -> Colour {
         return self * (1.0 - t) + (end * t);
     }
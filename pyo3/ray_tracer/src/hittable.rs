@@ -9,6 +9,9 @@ pub struct HitRecord {
     pub p: Point,
     pub normal: Vector,
     pub t: f64,
+    // Surface coordinates for texture lookups
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
     pub material: Arc<dyn Material>,
 }
@@ -25,6 +28,8 @@ impl HitRecord {
             p,
             normal,
             t,
+            u: 0.0,
+            v: 0.0,
             material,
             front_face: false,
         };
@@ -5,20 +5,71 @@ mod camera;
 mod colour;
 mod hittable;
 mod material;
+mod microfacet;
 mod point;
+mod principled;
 mod ray;
 mod render;
 mod sphere;
+mod texture;
 mod utils;
 mod vector;
 
 use render::render_spheres;
 use colour::Colour;
 use material::{Diffuse, Metal, Glass, Material};
+use principled::Principled;
+use texture::SolidColour;
 use sphere::{Sphere as RustSphere};
 use point::Point;
 
 
+// Scalar settings of the principled material, left unset for its defaults
+#[pyclass]
+#[derive(Clone, Default)]
+struct PrincipledProps {
+    metallic: Option<f64>,
+    roughness: Option<f64>,
+    specular: Option<f64>,
+    specular_tint: Option<f64>,
+    sheen: Option<f64>,
+    sheen_tint: Option<f64>,
+    clearcoat: Option<f64>,
+    clearcoat_gloss: Option<f64>,
+    transmission: Option<f64>,
+    emission: Option<(f64, f64, f64)>,
+}
+
+#[pymethods]
+impl PrincipledProps {
+    #[new]
+    fn new(
+        metallic: Option<f64>,
+        roughness: Option<f64>,
+        specular: Option<f64>,
+        specular_tint: Option<f64>,
+        sheen: Option<f64>,
+        sheen_tint: Option<f64>,
+        clearcoat: Option<f64>,
+        clearcoat_gloss: Option<f64>,
+        transmission: Option<f64>,
+        emission: Option<(f64, f64, f64)>,
+    ) -> Self {
+        return PrincipledProps {
+            metallic,
+            roughness,
+            specular,
+            specular_tint,
+            sheen,
+            sheen_tint,
+            clearcoat,
+            clearcoat_gloss,
+            transmission,
+            emission,
+        };
+    }
+}
+
 #[pyclass]
 #[derive(Clone)]
 struct MaterialProps {
@@ -26,22 +77,27 @@ struct MaterialProps {
     colour: Option<(f64, f64, f64)>,
     fuzz: Option<f64>,
     refractive_idx: Option<f64>,
+    principled: PrincipledProps,
 }
 
 #[pymethods]
 impl MaterialProps {
+    // Materials are principled unless another type is given
     #[new]
     fn new(
-        material_type: String,
+        material_type: Option<String>,
         colour: Option<(f64, f64, f64)>,
         fuzz: Option<f64>,
         refractive_idx: Option<f64>,
+        principled: Option<PrincipledProps>,
     ) -> Self {
-        let allowed = vec!["diffuse", "metal", "glass"];
+        let material_type = material_type.unwrap_or(String::from("principled"));
+        let allowed = vec!["diffuse", "metal", "glass", "principled"];
         if !allowed.contains(&material_type.to_lowercase().as_str()) {
             panic!("Invalid material type.");
         }
-        return MaterialProps { material_type, colour, fuzz, refractive_idx };
+        let principled = principled.unwrap_or_default();
+        return MaterialProps { material_type, colour, fuzz, refractive_idx, principled };
     }
 }
 
@@ -59,6 +115,36 @@ impl MaterialProps {
             "glass" => {
                 Glass::new(self.refractive_idx.unwrap())
             },
+            "principled" => {
+                let colour = self.colour.unwrap_or((0.8, 0.8, 0.8));
+                let props = &self.principled;
+                let mut material = Principled::new(
+                    SolidColour::new(Colour::new(colour.0, colour.1, colour.2)),
+                );
+                let scalars = [
+                    (props.metallic, &mut material.metallic),
+                    (props.roughness, &mut material.roughness),
+                    (props.specular, &mut material.specular),
+                    (props.specular_tint, &mut material.specular_tint),
+                    (props.sheen, &mut material.sheen),
+                    (props.sheen_tint, &mut material.sheen_tint),
+                    (props.clearcoat, &mut material.clearcoat),
+                    (props.clearcoat_gloss, &mut material.clearcoat_gloss),
+                    (props.transmission, &mut material.transmission),
+                ];
+                for (value, texture) in scalars.into_iter() {
+                    if let Some(value) = value {
+                        *texture = SolidColour::scalar(value);
+                    }
+                }
+                if let Some(emission) = props.emission {
+                    material.emission = SolidColour::new(Colour::new(emission.0, emission.1, emission.2));
+                }
+                if let Some(ior) = self.refractive_idx {
+                    material.ior = ior;
+                }
+                Arc::new(material)
+            },
             _ => panic!("Invalid material type."),
         };
     }
@@ -123,6 +209,7 @@ fn render_scene(
 fn ray_tracer(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<Sphere>()?;
     m.add_class::<MaterialProps>()?;
+    m.add_class::<PrincipledProps>()?;
     m.add_function(wrap_pyfunction!(sum_as_string, m)?)?;
     m.add_function(wrap_pyfunction!(render_scene, m)?)?;
     Ok(())
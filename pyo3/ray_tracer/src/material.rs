@@ -8,6 +8,11 @@ use crate::vector::random_in_unit_sphere;
 
 pub trait Material: Send + Sync {
     fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)>;
+
+    // Radiance given off by the surface itself
+    fn emitted(&self, _record: &HitRecord) -> Colour {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
 }
 
 pub struct Diffuse {
@@ -28,10 +28,11 @@ fn ray_colour(ray: &Ray, world: &Environment, depth: i32) -> Colour {
     }
 
     if let Some(rec) = world.hit(ray, 0.001, f64::INFINITY) {
+        let emitted = rec.material.emitted(&rec);
         if let Some((scattered, colour)) = rec.material.scatter(ray, &rec) {
-            return colour * ray_colour(&scattered, world, depth - 1);
+            return emitted + colour * ray_colour(&scattered, world, depth - 1);
         } else {
-            return Colour::new(0.0, 0.0, 0.0);
+            return emitted;
         }
     } else {
         let t = 0.5 * (ray.direction.unit().y() + 1.0);
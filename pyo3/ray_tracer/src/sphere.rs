@@ -1,3 +1,4 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 use crate::hittable::{Hit, HitRecord};
@@ -43,7 +44,10 @@ impl Hit for Sphere {
 
         let p = ray.at(t);
         let normal = (p - self.centre) / self.radius;
-        let rec = HitRecord::new_from_ray(p, normal, t, ray, self.material.clone());
+        let mut rec = HitRecord::new_from_ray(p, normal, t, ray, self.material.clone());
+        // Longitude and latitude, with v running up from the bottom of the sphere
+        rec.u = ((-normal.z()).atan2(normal.x()) + PI) / (2.0 * PI);
+        rec.v = (-normal.y()).clamp(-1.0, 1.0).acos() / PI;
         return Some(rec);
     }
 }
@@ -0,0 +1,29 @@
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::point::Point;
+
+// A colour varying over a surface. Scalar parameters read a texture's red channel.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Point) -> Colour;
+}
+
+pub struct SolidColour {
+    pub colour: Colour,
+}
+
+impl SolidColour {
+    pub fn new(colour: Colour) -> Arc<SolidColour> {
+        return Arc::new(SolidColour { colour });
+    }
+
+    pub fn scalar(value: f64) -> Arc<SolidColour> {
+        return SolidColour::new(Colour::new(value, value, value));
+    }
+}
+
+impl Texture for SolidColour {
+    fn value(&self, _u: f64, _v: f64, _p: Point) -> Colour {
+        return self.colour;
+    }
+}
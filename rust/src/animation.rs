@@ -0,0 +1,199 @@
+use crate::point::Point;
+use crate::vector::Vector;
+
+// Values that can be blended between keyframes
+pub trait Animatable: Copy {
+    fn add(self, other: Self) -> Self;
+    fn scale(self, k: f64) -> Self;
+}
+
+impl Animatable for f64 {
+    fn add(self, other: f64) -> f64 {
+        return self + other;
+    }
+
+    fn scale(self, k: f64) -> f64 {
+        return self * k;
+    }
+}
+
+impl Animatable for Vector {
+    fn add(self, other: Vector) -> Vector {
+        return self + other;
+    }
+
+    fn scale(self, k: f64) -> Vector {
+        return self * k;
+    }
+}
+
+impl Animatable for Point {
+    fn add(self, other: Point) -> Point {
+        return Point::from(self.v + other.v);
+    }
+
+    fn scale(self, k: f64) -> Point {
+        return Point::from(self.v * k);
+    }
+}
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Interpolation {
+    Linear,
+    CatmullRom,
+    // Cubic Bezier segments, using each keyframe's handles or Catmull-Rom tangents when unset
+    Bezier,
+}
+
+#[derive(Clone, Copy)]
+pub struct Keyframe<T: Animatable> {
+    pub time: f64,
+    pub value: T,
+    pub handles: Option<(T, T)>,
+}
+
+impl<T: Animatable> Keyframe<T> {
+    pub fn new(time: f64, value: T) -> Keyframe<T> {
+        return Keyframe {
+            time,
+            value,
+            handles: None,
+        };
+    }
+
+    // Control points for the incoming and outgoing Bezier segments
+    #[allow(dead_code)]
+    pub fn with_handles(mut self, handle_in: T, handle_out: T) -> Keyframe<T> {
+        self.handles = Some((handle_in, handle_out));
+        return self;
+    }
+}
+
+pub struct Track<T: Animatable> {
+    keys: Vec<Keyframe<T>>,
+    interpolation: Interpolation,
+}
+
+impl<T: Animatable> Track<T> {
+    pub fn new(mut keys: Vec<Keyframe<T>>, interpolation: Interpolation) -> Track<T> {
+        assert!(!keys.is_empty(), "Tracks need at least one keyframe.");
+        keys.sort_by(|a, b| a.time.total_cmp(&b.time));
+        return Track {
+            keys,
+            interpolation,
+        };
+    }
+
+    // Holds the first and last values outside of the keyed range
+    pub fn evaluate(&self, time: f64) -> T {
+        let n = self.keys.len();
+        if time <= self.keys[0].time {
+            return self.keys[0].value;
+        }
+        if time >= self.keys[n - 1].time {
+            return self.keys[n - 1].value;
+        }
+
+        let i = self.keys.partition_point(|k| k.time <= time) - 1;
+        let (k1, k2) = (&self.keys[i], &self.keys[i + 1]);
+        let t = (time - k1.time) / (k2.time - k1.time);
+        let p0 = self.keys[i.saturating_sub(1)].value;
+        let (p1, p2) = (k1.value, k2.value);
+        let p3 = self.keys[(i + 2).min(n - 1)].value;
+
+        return match self.interpolation {
+            Interpolation::Linear => p1.scale(1.0 - t).add(p2.scale(t)),
+            Interpolation::CatmullRom => catmull_rom(p0, p1, p2, p3, t),
+            Interpolation::Bezier => {
+                let c1 = match k1.handles {
+                    Some((_, out)) => out,
+                    None => p1.add(p2.add(p0.scale(-1.0)).scale(1.0 / 6.0)),
+                };
+                let c2 = match k2.handles {
+                    Some((handle_in, _)) => handle_in,
+                    None => p2.add(p3.add(p1.scale(-1.0)).scale(-1.0 / 6.0)),
+                };
+                bezier(p1, c1, c2, p2, t)
+            }
+        };
+    }
+}
+
+fn catmull_rom<T: Animatable>(p0: T, p1: T, p2: T, p3: T, t: f64) -> T {
+    let (t2, t3) = (t * t, t * t * t);
+    return p0
+        .scale(-0.5 * t3 + t2 - 0.5 * t)
+        .add(p1.scale(1.5 * t3 - 2.5 * t2 + 1.0))
+        .add(p2.scale(-1.5 * t3 + 2.0 * t2 + 0.5 * t))
+        .add(p3.scale(0.5 * t3 - 0.5 * t2));
+}
+
+fn bezier<T: Animatable>(p0: T, c1: T, c2: T, p1: T, t: f64) -> T {
+    let s = 1.0 - t;
+    return p0
+        .scale(s * s * s)
+        .add(c1.scale(3.0 * s * s * t))
+        .add(c2.scale(3.0 * s * t * t))
+        .add(p1.scale(t * t * t));
+}
+
+// Camera tracks left unset keep the scene's static values
+pub struct CameraTracks {
+    pub look_from: Option<Track<Point>>,
+    pub look_at: Option<Track<Point>>,
+    pub v_fov: Option<Track<f64>>,
+    pub focus_distance: Option<Track<f64>>,
+}
+
+// Frames to render, inclusive of the end frame
+#[derive(Clone, Copy)]
+pub struct FrameRange {
+    pub start: u32,
+    pub end: u32,
+    pub step: u32,
+}
+
+impl FrameRange {
+    pub fn frames(&self) -> impl Iterator<Item = u32> {
+        return (self.start..=self.end).step_by(self.step.max(1) as usize);
+    }
+}
+
+#[test]
+fn test_tracks() {
+    let keys = vec![
+        Keyframe::new(0.0, 0.0),
+        Keyframe::new(1.0, 1.0),
+        Keyframe::new(2.0, 4.0),
+        Keyframe::new(3.0, 9.0),
+    ];
+    let linear = Track::new(keys.clone(), Interpolation::Linear);
+    assert_eq!(linear.evaluate(-1.0), 0.0);
+    assert_eq!(linear.evaluate(1.5), 2.5);
+    assert_eq!(linear.evaluate(5.0), 9.0);
+
+    // Catmull-Rom reproduces quadratics between interior keys
+    let spline = Track::new(keys.clone(), Interpolation::CatmullRom);
+    assert_eq!(spline.evaluate(1.5), 2.25);
+    assert_eq!(spline.evaluate(2.0), 4.0);
+
+    let bezier = Track::new(keys, Interpolation::Bezier);
+    assert!((bezier.evaluate(1.5) - 2.25).abs() < 1e-12);
+    let eased = Track::new(
+        vec![
+            Keyframe::new(0.0, 0.0).with_handles(0.0, 0.0),
+            Keyframe::new(1.0, 1.0).with_handles(1.0, 1.0),
+        ],
+        Interpolation::Bezier,
+    );
+    assert_eq!(eased.evaluate(0.5), 0.5);
+    assert!(eased.evaluate(0.1) < 0.1);
+
+    let range = FrameRange {
+        start: 1,
+        end: 10,
+        step: 4,
+    };
+    assert_eq!(range.frames().collect::<Vec<u32>>(), vec![1, 5, 9]);
+}
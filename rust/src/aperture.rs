@@ -0,0 +1,113 @@
+use rand::Rng;
+use std::f64::consts::PI;
+use std::io;
+
+use crate::image::Image;
+use crate::sampling::Distribution1D;
+use crate::utils::deg_to_rad;
+use crate::vector::random_in_unit_disk;
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum ApertureShape {
+    Circle,
+    // Regular polygon with its rotation in degrees
+    Polygon { blades: u32, rotation: f64 },
+    // Path to a PPM image whose brightness gives the aperture's transmission
+    Mask(&'static str),
+}
+
+pub enum Aperture {
+    Circle,
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    Mask {
+        width: u32,
+        height: u32,
+        distribution: Distribution1D,
+    },
+}
+
+impl Aperture {
+    pub fn new(shape: ApertureShape) -> io::Result<Aperture> {
+        return Ok(match shape {
+            ApertureShape::Circle => Aperture::Circle,
+            ApertureShape::Polygon { blades, rotation } => Aperture::Polygon {
+                blades: blades.max(3),
+                rotation: deg_to_rad(rotation),
+            },
+            ApertureShape::Mask(path) => {
+                let image = Image::load_ppm(path)?;
+                let mut weights = Vec::with_capacity((image.width * image.height) as usize);
+                for y in 0..image.height {
+                    for x in 0..image.width {
+                        weights.push(image.get(x, y).luminance());
+                    }
+                }
+                Aperture::Mask {
+                    width: image.width,
+                    height: image.height,
+                    distribution: Distribution1D::new(weights),
+                }
+            }
+        });
+    }
+
+    // Samples a point on the aperture, scaled so that it fits within the unit disk
+    pub fn sample(&self) -> (f64, f64) {
+        let mut rng = rand::thread_rng();
+        return match self {
+            Aperture::Circle => {
+                let p = random_in_unit_disk();
+                (p.x(), p.y())
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // Picks one of the equal triangles fanning out from the centre, then a point within it
+                let wedge = 2.0 * PI / (*blades as f64);
+                let k = rng.gen_range(0..*blades) as f64;
+                let (a0, a1) = (rotation + k * wedge, rotation + (k + 1.0) * wedge);
+                let (mut r1, mut r2): (f64, f64) = (rng.gen(), rng.gen());
+                if r1 + r2 > 1.0 {
+                    (r1, r2) = (1.0 - r1, 1.0 - r2);
+                }
+                (r1 * a0.cos() + r2 * a1.cos(), r1 * a0.sin() + r2 * a1.sin())
+            }
+            Aperture::Mask {
+                width,
+                height,
+                distribution,
+            } => {
+                let (_, _, idx) = distribution.sample(rng.gen());
+                let x = (idx as u32 % width) as f64 + rng.gen::<f64>();
+                let y = (idx as u32 / width) as f64 + rng.gen::<f64>();
+                // Image rows run downwards, and the mask's square is inscribed in the unit disk
+                let scale = std::f64::consts::FRAC_1_SQRT_2;
+                (
+                    (2.0 * x / *width as f64 - 1.0) * scale,
+                    (1.0 - 2.0 * y / *height as f64) * scale,
+                )
+            }
+        };
+    }
+}
+
+#[test]
+fn test_aperture() {
+    let hexagon = Aperture::new(ApertureShape::Polygon {
+        blades: 6,
+        rotation: 0.0,
+    })
+    .unwrap();
+    // The inscribed radius of a unit hexagon is cos(30 degrees)
+    let inner = (PI / 6.0).cos();
+    let mut beyond_inner = false;
+    for _ in 0..1000 {
+        let (x, y) = hexagon.sample();
+        let r = (x * x + y * y).sqrt();
+        assert!(r <= 1.0 + 1e-12);
+        beyond_inner |= r > inner;
+    }
+    assert!(beyond_inner);
+}
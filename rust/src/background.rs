@@ -0,0 +1,139 @@
+use rand::Rng;
+use std::f64::consts::PI;
+
+use crate::colour::Colour;
+use crate::image::Image;
+use crate::sampling::Distribution2D;
+use crate::utils::deg_to_rad;
+use crate::vector::Vector;
+
+// Radiance arriving from infinitely far away, for rays that escape the scene
+pub trait Background: Send + Sync {
+    fn colour(&self, direction: Vector) -> Colour;
+
+    // Importance samples a unit direction towards the background, returning its radiance and
+    // solid angle pdf. Backgrounds that return None are only reached by scattered rays.
+    fn sample(&self) -> Option<(Vector, Colour, f64)> {
+        return None;
+    }
+
+    fn pdf(&self, _direction: Vector) -> f64 {
+        return 0.0;
+    }
+}
+
+pub struct Gradient {
+    pub start: Colour,
+    pub end: Colour,
+}
+
+impl Background for Gradient {
+    fn colour(&self, direction: Vector) -> Colour {
+        let t = 0.5 * (direction.unit().y() + 1.0);
+        return self.start.interpolate(self.end, t);
+    }
+}
+
+// Equirectangular image lighting, with +y up and the image's centre facing -z
+pub struct EnvironmentMap {
+    image: Image,
+    rotation: f64,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    // Rotation is in degrees about the vertical axis
+    pub fn new(image: Image, rotation: f64, intensity: f64) -> EnvironmentMap {
+        // Rows near the poles cover less solid angle, so are less likely to be chosen
+        let mut weights = Vec::with_capacity((image.width * image.height) as usize);
+        for y in 0..image.height {
+            let sin_theta = (PI * (y as f64 + 0.5) / image.height as f64).sin();
+            for x in 0..image.width {
+                weights.push(image.get(x, y).luminance() * sin_theta);
+            }
+        }
+        let distribution =
+            Distribution2D::new(&weights, image.width as usize, image.height as usize);
+        return EnvironmentMap {
+            image,
+            rotation: deg_to_rad(rotation),
+            intensity,
+            distribution,
+        };
+    }
+
+    fn direction_to_uv(&self, direction: Vector) -> (f64, f64) {
+        let d = direction.unit();
+        let phi = d.x().atan2(-d.z()) + self.rotation;
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        let v = d.y().clamp(-1.0, 1.0).acos() / PI;
+        return (u, v);
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vector {
+        let phi = (u - 0.5) * 2.0 * PI - self.rotation;
+        let theta = v * PI;
+        return Vector::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            -theta.sin() * phi.cos(),
+        );
+    }
+
+    fn lookup(&self, u: f64, v: f64) -> Colour {
+        let x = (u * self.image.width as f64) as u32;
+        let y = (v * self.image.height as f64) as u32;
+        return self.image.get(x, y) * self.intensity;
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn colour(&self, direction: Vector) -> Colour {
+        let (u, v) = self.direction_to_uv(direction);
+        return self.lookup(u, v);
+    }
+
+    fn sample(&self) -> Option<(Vector, Colour, f64)> {
+        let mut rng = rand::thread_rng();
+        let (u, v, pdf_uv) = self.distribution.sample(rng.gen(), rng.gen());
+        let sin_theta = (v * PI).sin();
+        if pdf_uv == 0.0 || sin_theta == 0.0 {
+            return None;
+        }
+        // Converts from density over the image to density over solid angle
+        let pdf = pdf_uv / (2.0 * PI * PI * sin_theta);
+        return Some((self.uv_to_direction(u, v), self.lookup(u, v), pdf));
+    }
+
+    fn pdf(&self, direction: Vector) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        return self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta);
+    }
+}
+
+#[test]
+fn test_environment_map() {
+    // A dim map with one bright pixel, which should take nearly all of the samples
+    let mut pixels = vec![Colour::new(0.01, 0.01, 0.01); 8 * 4];
+    pixels[8 + 5] = Colour::new(100.0, 100.0, 100.0);
+    let map = EnvironmentMap::new(Image::new(8, 4, pixels), 30.0, 2.0);
+
+    let (u, v) = map.direction_to_uv(map.uv_to_direction(0.3, 0.7));
+    assert!((u - 0.3).abs() < 1e-9 && (v - 0.7).abs() < 1e-9);
+
+    let mut bright = 0;
+    for _ in 0..100 {
+        let (direction, colour, pdf) = map.sample().unwrap();
+        assert!((pdf - map.pdf(direction)).abs() < 1e-6 * pdf);
+        assert_eq!(colour.r, map.colour(direction).r);
+        if colour.r == 200.0 {
+            bright += 1;
+        }
+    }
+    assert!(bright > 90);
+}
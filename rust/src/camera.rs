@@ -1,61 +1,402 @@
+use rand::Rng;
+use std::f64::consts::PI;
+
+use crate::aperture::Aperture;
+use crate::colour::Colour;
+use crate::hittable::Hit;
 use crate::point::Point;
 use crate::ray::Ray;
-use crate::utils::deg_to_rad;
-use crate::vector::{random_in_unit_disk, Vector};
+use crate::utils::{deg_to_rad, rad_to_deg};
+use crate::vector::Vector;
+
+pub trait Camera: Send + Sync {
+    // Maps (s, t) in [0, 1], measured from the bottom left of the image, to a primary ray.
+    // Returns None where the projection does not cover the image, e.g. outside a fisheye's circle.
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray>;
+
+    // Like `get_ray`, along with a weight for the radiance carried back along the ray
+    fn sample_ray(&self, s: f64, t: f64) -> Option<(Ray, Colour)> {
+        return self
+            .get_ray(s, t)
+            .map(|ray| (ray, Colour::new(1.0, 1.0, 1.0)));
+    }
+}
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum FisheyeMapping {
+    Equidistant,
+    Equisolid,
+}
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Projection {
+    Perspective,
+    // Height of the view in scene units
+    Orthographic { height: f64 },
+    // Field of view in degrees across the image circle, which fits the image height
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    Equirectangular,
+}
+
+// Orthonormal camera basis, with w pointing backwards from the view direction
+#[derive(Clone, Copy)]
+struct Frame {
+    u: Vector,
+    v: Vector,
+    w: Vector,
+}
+
+impl Frame {
+    fn new(look_from: Point, look_at: Point, v_up: Vector) -> Frame {
+        let w = (look_from - look_at).unit();
+        let u = v_up.cross(w).unit();
+        let v = w.cross(u);
+        return Frame { u, v, w };
+    }
+}
+
+// Physical lens description, with lengths in millimetres and scene units treated as metres
+#[derive(Clone, Copy)]
+pub struct Lens {
+    pub focal_length: f64,
+    pub sensor_height: f64,
+    pub f_number: f64,
+}
+
+impl Lens {
+    pub fn v_fov(&self) -> f64 {
+        return rad_to_deg(2.0 * (self.sensor_height / (2.0 * self.focal_length)).atan());
+    }
+
+    pub fn aperture(&self) -> f64 {
+        return (self.focal_length / self.f_number) / 1000.0;
+    }
+}
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Focus {
+    LookAt,
+    Distance(f64),
+    // Focuses on the first surface hit through the centre of the image
+    Auto,
+}
+
+impl Focus {
+    pub fn distance(&self, look_from: Point, look_at: Point, world: &dyn Hit) -> f64 {
+        let look_at_distance = (look_at - look_from).length();
+        return match *self {
+            Focus::LookAt => look_at_distance,
+            Focus::Distance(distance) => distance,
+            Focus::Auto => {
+                let ray = Ray::new(look_from, (look_at - look_from).unit());
+                match world.hit(&ray, 0.001, f64::INFINITY) {
+                    Some(rec) => rec.t,
+                    None => look_at_distance,
+                }
+            }
+        };
+    }
+}
+
+// Lens imperfections that shape out of focus highlights
+pub struct Bokeh {
+    pub aperture: Aperture,
+    // Offset of the clipping pupil at the image corners, as a fraction of the aperture radius
+    pub cat_eye: f64,
+    // Relative difference in magnification between the red and blue channels
+    pub chromatic_aberration: f64,
+}
 
-pub struct Camera {
+pub struct Perspective {
     look_from: Point,
     horizontal: Vector,
     vertical: Vector,
     lower_left_corner: Point,
     aperture: f64,
+    focus_distance: f64,
     u: Vector,
     v: Vector,
+    bokeh: Bokeh,
 }
 
-impl Camera {
+impl Perspective {
     pub fn new(
         look_from: Point,
         look_at: Point,
         v_up: Vector,
         aspect_ratio: f64,
-        v_fov: i32,
+        v_fov: f64,
         aperture: f64,
-    ) -> Camera {
+        focus_distance: f64,
+    ) -> Perspective {
         let theta = deg_to_rad(v_fov);
-        let focus_distance = (look_from - look_at).length();
         let viewport_height = 2.0 * (theta / 2.0).tan();
         let viewport_width = aspect_ratio * viewport_height;
 
-        let w = (look_from - look_at).unit();
-        let u = v_up.cross(w).unit();
-        let v = w.cross(u);
+        let Frame { u, v, w } = Frame::new(look_from, look_at, v_up);
 
         let horizontal = focus_distance * viewport_width * u;
         let vertical = focus_distance * viewport_height * v;
         let lower_left_corner =
             look_from - (horizontal / 2.0) - (vertical / 2.0) - (focus_distance * w);
 
-        return Camera {
+        return Perspective {
             look_from,
             horizontal,
             vertical,
             lower_left_corner,
             aperture,
+            focus_distance,
             u,
             v,
+            bokeh: Bokeh {
+                aperture: Aperture::Circle,
+                cat_eye: 0.0,
+                chromatic_aberration: 0.0,
+            },
         };
     }
 
-    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = (self.aperture / 2.0) * random_in_unit_disk();
-        let offset = (self.u * rd.x()) + (self.v * rd.y());
+    pub fn with_bokeh(mut self, bokeh: Bokeh) -> Perspective {
+        self.bokeh = bokeh;
+        return self;
+    }
 
-        return Ray::new(
+    // Shifts the frustum sideways, in scene units per unit of distance from the camera
+    pub fn with_shift(mut self, shift: f64) -> Perspective {
+        self.lower_left_corner = self.lower_left_corner + self.u * (shift * self.focus_distance);
+        return self;
+    }
+
+    pub fn from_lens(
+        look_from: Point,
+        look_at: Point,
+        v_up: Vector,
+        aspect_ratio: f64,
+        lens: Lens,
+        focus_distance: f64,
+    ) -> Perspective {
+        return Perspective::new(
+            look_from,
+            look_at,
+            v_up,
+            aspect_ratio,
+            lens.v_fov(),
+            lens.aperture(),
+            focus_distance,
+        );
+    }
+}
+
+impl Camera for Perspective {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let (x, y) = self.bokeh.aperture.sample();
+
+        // Off axis, the lens barrel clips the aperture into a cat's eye and darkens the corners
+        if self.bokeh.cat_eye > 0.0 {
+            let dx = x - self.bokeh.cat_eye * (2.0 * s - 1.0);
+            let dy = y - self.bokeh.cat_eye * (2.0 * t - 1.0);
+            if dx * dx + dy * dy > 1.0 {
+                return None;
+            }
+        }
+
+        let radius = self.aperture / 2.0;
+        let offset = (self.u * (radius * x)) + (self.v * (radius * y));
+        return Some(Ray::new(
             self.look_from + offset,
             self.lower_left_corner + (self.horizontal * s) + (self.vertical * t)
                 - self.look_from
                 - offset,
-        );
+        ));
+    }
+
+    fn sample_ray(&self, s: f64, t: f64) -> Option<(Ray, Colour)> {
+        if self.bokeh.chromatic_aberration == 0.0 {
+            return self
+                .get_ray(s, t)
+                .map(|ray| (ray, Colour::new(1.0, 1.0, 1.0)));
+        }
+
+        // Traces a single channel per ray, magnified about the image centre by its own amount
+        let channel = rand::thread_rng().gen_range(0..3);
+        let magnification = 1.0 + self.bokeh.chromatic_aberration * (1.0 - channel as f64) / 2.0;
+        let weight = match channel {
+            0 => Colour::new(3.0, 0.0, 0.0),
+            1 => Colour::new(0.0, 3.0, 0.0),
+            _ => Colour::new(0.0, 0.0, 3.0),
+        };
+        let s = 0.5 + (s - 0.5) / magnification;
+        let t = 0.5 + (t - 0.5) / magnification;
+        return self.get_ray(s, t).map(|ray| (ray, weight));
+    }
+}
+
+pub struct Orthographic {
+    look_from: Point,
+    frame: Frame,
+    width: f64,
+    height: f64,
+}
+
+impl Orthographic {
+    pub fn new(
+        look_from: Point,
+        look_at: Point,
+        v_up: Vector,
+        aspect_ratio: f64,
+        height: f64,
+    ) -> Orthographic {
+        return Orthographic {
+            look_from,
+            frame: Frame::new(look_from, look_at, v_up),
+            width: aspect_ratio * height,
+            height,
+        };
+    }
+}
+
+impl Camera for Orthographic {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let origin = self.look_from
+            + (self.frame.u * ((s - 0.5) * self.width))
+            + (self.frame.v * ((t - 0.5) * self.height));
+        return Some(Ray::new(origin, -self.frame.w));
+    }
+}
+
+pub struct Fisheye {
+    look_from: Point,
+    frame: Frame,
+    aspect_ratio: f64,
+    theta_max: f64,
+    mapping: FisheyeMapping,
+}
+
+impl Fisheye {
+    pub fn new(
+        look_from: Point,
+        look_at: Point,
+        v_up: Vector,
+        aspect_ratio: f64,
+        fov: f64,
+        mapping: FisheyeMapping,
+    ) -> Fisheye {
+        return Fisheye {
+            look_from,
+            frame: Frame::new(look_from, look_at, v_up),
+            aspect_ratio,
+            theta_max: deg_to_rad(fov) / 2.0,
+            mapping,
+        };
+    }
+}
+
+impl Camera for Fisheye {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        // Position on the image plane, where the image circle has radius 1
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.theta_max,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.theta_max / 2.0).sin()).asin(),
+        };
+        let phi = y.atan2(x);
+        let direction = (self.frame.u * (theta.sin() * phi.cos()))
+            + (self.frame.v * (theta.sin() * phi.sin()))
+            - (self.frame.w * theta.cos());
+        return Some(Ray::new(self.look_from, direction));
     }
 }
+
+// Full 360 by 180 degree panorama, best rendered with a 2:1 aspect ratio
+pub struct Equirectangular {
+    look_from: Point,
+    frame: Frame,
+    eye_offset: f64,
+}
+
+impl Equirectangular {
+    pub fn new(look_from: Point, look_at: Point, v_up: Vector) -> Equirectangular {
+        return Equirectangular {
+            look_from,
+            frame: Frame::new(look_from, look_at, v_up),
+            eye_offset: 0.0,
+        };
+    }
+
+    // Moves each ray's origin around a circle of this radius, tangent to the viewing direction,
+    // for omni-directional stereo. Negative offsets give the left eye.
+    pub fn with_eye_offset(mut self, eye_offset: f64) -> Equirectangular {
+        self.eye_offset = eye_offset;
+        return self;
+    }
+}
+
+impl Camera for Equirectangular {
+    fn get_ray(&self, s: f64, t: f64) -> Option<Ray> {
+        let phi = (s - 0.5) * 2.0 * PI;
+        let theta = (t - 0.5) * PI;
+        let direction = (self.frame.u * (theta.cos() * phi.sin())) + (self.frame.v * theta.sin())
+            - (self.frame.w * (theta.cos() * phi.cos()));
+        let tangent = (self.frame.u * phi.cos()) + (self.frame.w * phi.sin());
+        return Some(Ray::new(
+            self.look_from + tangent * self.eye_offset,
+            direction,
+        ));
+    }
+}
+
+#[test]
+fn test_camera() {
+    let lens = Lens {
+        focal_length: 50.0,
+        sensor_height: 24.0,
+        f_number: 2.0,
+    };
+    assert!((lens.v_fov() - 26.9915).abs() < 1e-4);
+    assert_eq!(lens.aperture(), 0.025);
+
+    let mut world = crate::hittable::Environment {
+        hittables: Vec::new(),
+    };
+    let material = crate::material::Diffuse::new(crate::colour::Colour::new(0.5, 0.5, 0.5));
+    world.add(crate::sphere::Sphere::new(
+        Point::new(0.0, 0.0, -5.0),
+        1.0,
+        material,
+    ));
+    let look_from = Point::new(0.0, 0.0, 0.0);
+    let look_at = Point::new(0.0, 0.0, -10.0);
+    assert_eq!(Focus::LookAt.distance(look_from, look_at, &world), 10.0);
+    assert_eq!(Focus::Auto.distance(look_from, look_at, &world), 4.0);
+
+    let v_up = Vector::new(0.0, 1.0, 0.0);
+    let fisheye = Fisheye::new(
+        look_from,
+        look_at,
+        v_up,
+        1.0,
+        180.0,
+        FisheyeMapping::Equisolid,
+    );
+    let centre = fisheye.get_ray(0.5, 0.5).unwrap().direction;
+    assert_eq!((centre.x(), centre.y(), centre.z()), (0.0, 0.0, -1.0));
+    let edge = fisheye.get_ray(1.0, 0.5).unwrap().direction;
+    assert!(edge.x() > 0.9999 && edge.z().abs() < 1e-9);
+    assert!(fisheye.get_ray(1.0, 1.0).is_none());
+
+    let panorama = Equirectangular::new(look_from, look_at, v_up);
+    let behind = panorama.get_ray(0.0, 0.5).unwrap().direction;
+    assert!((behind.z() - 1.0).abs() < 1e-9);
+    let up = panorama.get_ray(0.25, 1.0).unwrap().direction;
+    assert!((up.y() - 1.0).abs() < 1e-9);
+}
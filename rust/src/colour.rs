@@ -3,6 +3,7 @@ use std::fmt;
 use std::fmt::Display;
 use std::ops::{Add, AddAssign, Mul, Range};
 
+// Linear RGB in the renderer's working colour space, which is sRGB's unless chosen otherwise
 #[derive(Clone, Copy)]
 pub struct Colour {
     pub r: f64,
@@ -15,8 +16,7 @@ impl Colour {
         return Colour { r, g, b };
     }
 
-    pub fn random() -> Colour {
-        let mut rng = rand::thread_rng();
+    pub fn random(rng: &mut impl Rng) -> Colour {
         return Colour {
             r: rng.gen(),
             g: rng.gen(),
@@ -24,8 +24,7 @@ impl Colour {
         };
     }
 
-    pub fn random_range(range: Range<f64>) -> Colour {
-        let mut rng = rand::thread_rng();
+    pub fn random_range(rng: &mut impl Rng, range: Range<f64>) -> Colour {
         return Colour {
             r: rng.gen_range(range.clone()),
             g: rng.gen_range(range.clone()),
@@ -41,6 +40,18 @@ impl Colour {
         return format!("{} {} {}", ir, ig, ib);
     }
 
+    // Formats a colour that's already been encoded for output as 8 bit values
+    pub fn render_encoded(self) -> String {
+        let ir = (256.0 * self.r.clamp(0.0, 0.999)) as u64;
+        let ig = (256.0 * self.g.clamp(0.0, 0.999)) as u64;
+        let ib = (256.0 * self.b.clamp(0.0, 0.999)) as u64;
+        return format!("{} {} {}", ir, ig, ib);
+    }
+
+    pub fn luminance(self) -> f64 {
+        return 0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b;
+    }
+
     pub fn interpolate(self, end: Colour, t: f64) -> Colour {
         return self * (1.0 - t) + (end * t);
     }
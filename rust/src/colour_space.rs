@@ -0,0 +1,247 @@
+use crate::colour::Colour;
+use crate::lut::Lut;
+
+pub type Matrix = [[f64; 3]; 3];
+
+// RGB spaces colours can be given in, rendered in, or written out to. Each is defined by the
+// chromaticities of its primaries and white point, and linear here; encodings are separate.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(dead_code)]
+pub enum ColourSpace {
+    // The primaries of sRGB and Rec.709, which colours are assumed to be in unless stated
+    LinearSrgb,
+    // ACES AP1, a wide gamut working space for rendering and compositing
+    AcesCg,
+    Rec2020,
+    DisplayP3,
+}
+
+const D65: (f64, f64) = (0.3127, 0.3290);
+const ACES_WHITE: (f64, f64) = (0.32168, 0.33767);
+
+// Cone response space used to adapt colours between white points
+const BRADFORD: Matrix = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+impl ColourSpace {
+    // Chromaticities of the red, green and blue primaries, then the white point
+    fn chromaticities(self) -> [(f64, f64); 4] {
+        return match self {
+            ColourSpace::LinearSrgb => [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06), D65],
+            ColourSpace::AcesCg => [(0.713, 0.293), (0.165, 0.830), (0.128, 0.044), ACES_WHITE],
+            ColourSpace::Rec2020 => [(0.708, 0.292), (0.170, 0.797), (0.131, 0.046), D65],
+            ColourSpace::DisplayP3 => [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060), D65],
+        };
+    }
+
+    fn white_xyz(self) -> [f64; 3] {
+        let (x, y) = self.chromaticities()[3];
+        return [x / y, 1.0, (1.0 - x - y) / y];
+    }
+
+    // Takes RGB in this space to CIE XYZ, scaling the primaries so that white has Y = 1
+    pub fn rgb_to_xyz(self) -> Matrix {
+        let c = self.chromaticities();
+        let columns = [c[0], c[1], c[2]].map(|(x, y)| [x / y, 1.0, (1.0 - x - y) / y]);
+        let primaries = transpose(columns);
+        let scales = multiply_vector(invert(primaries), self.white_xyz());
+        return transpose([0, 1, 2].map(|i| columns[i].map(|v| v * scales[i])));
+    }
+
+    pub fn xyz_to_rgb(self) -> Matrix {
+        return invert(self.rgb_to_xyz());
+    }
+
+    // Takes RGB in this space to another, adapting between their white points so that white
+    // stays white
+    pub fn matrix_to(self, other: ColourSpace) -> Matrix {
+        let (source, target) = (self.white_xyz(), other.white_xyz());
+        let (source, target) = (
+            multiply_vector(BRADFORD, source),
+            multiply_vector(BRADFORD, target),
+        );
+        let mut scale = [[0.0; 3]; 3];
+        for i in 0..3 {
+            scale[i][i] = target[i] / source[i];
+        }
+        let adapt = multiply(invert(BRADFORD), multiply(scale, BRADFORD));
+        return multiply(other.xyz_to_rgb(), multiply(adapt, self.rgb_to_xyz()));
+    }
+
+    pub fn convert(self, colour: Colour, other: ColourSpace) -> Colour {
+        if self == other {
+            return colour;
+        }
+        return apply(self.matrix_to(other), colour);
+    }
+}
+
+// Transfer functions between linear values and how they're stored in files
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+    Linear,
+    // The piecewise sRGB curve, also used by Display P3
+    Srgb,
+    // A pure power law, where values are stored raised to 1 / gamma
+    Gamma(f64),
+}
+
+impl Encoding {
+    pub fn encode(self, value: f64) -> f64 {
+        return match self {
+            Encoding::Linear => value,
+            Encoding::Srgb => {
+                if value <= 0.0031308 {
+                    12.92 * value
+                } else {
+                    1.055 * value.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            Encoding::Gamma(gamma) => value.max(0.0).powf(1.0 / gamma),
+        };
+    }
+
+    pub fn decode(self, value: f64) -> f64 {
+        return match self {
+            Encoding::Linear => value,
+            Encoding::Srgb => {
+                if value <= 0.04045 {
+                    value / 12.92
+                } else {
+                    ((value + 0.055) / 1.055).powf(2.4)
+                }
+            }
+            Encoding::Gamma(gamma) => value.max(0.0).powf(gamma),
+        };
+    }
+
+    pub fn encode_colour(self, colour: Colour) -> Colour {
+        return Colour::new(
+            self.encode(colour.r),
+            self.encode(colour.g),
+            self.encode(colour.b),
+        );
+    }
+
+    pub fn decode_colour(self, colour: Colour) -> Colour {
+        return Colour::new(
+            self.decode(colour.r),
+            self.decode(colour.g),
+            self.decode(colour.b),
+        );
+    }
+}
+
+// Takes rendered colours from the working space to what's written to the output image: into
+// the output's colour space, through its encoding, and then through a look, if there is one,
+// which is applied to the encoded values as grading tools expect
+pub struct OutputTransform {
+    pub space: ColourSpace,
+    pub encoding: Encoding,
+    pub look: Option<Lut>,
+}
+
+impl OutputTransform {
+    pub fn apply(&self, colour: Colour, working: ColourSpace) -> Colour {
+        let colour = working.convert(colour, self.space);
+        let colour = Colour::new(colour.r.max(0.0), colour.g.max(0.0), colour.b.max(0.0));
+        let encoded = self.encoding.encode_colour(colour);
+        return match &self.look {
+            Some(lut) => lut.apply(encoded),
+            None => encoded,
+        };
+    }
+}
+
+pub fn apply(m: Matrix, colour: Colour) -> Colour {
+    let [r, g, b] = multiply_vector(m, [colour.r, colour.g, colour.b]);
+    return Colour::new(r, g, b);
+}
+
+fn multiply_vector(m: Matrix, v: [f64; 3]) -> [f64; 3] {
+    return m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2]);
+}
+
+fn multiply(a: Matrix, b: Matrix) -> Matrix {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    return out;
+}
+
+fn transpose(m: Matrix) -> Matrix {
+    return [0, 1, 2].map(|i| [m[0][i], m[1][i], m[2][i]]);
+}
+
+fn invert(m: Matrix) -> Matrix {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| -> f64 {
+        return m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    };
+    let det = m[0][0] * cofactor(1, 2, 1, 2) - m[0][1] * cofactor(1, 2, 0, 2)
+        + m[0][2] * cofactor(1, 2, 0, 1);
+    let adjugate = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    return adjugate.map(|row| row.map(|v| v / det));
+}
+
+#[test]
+fn test_colour_spaces() {
+    let close = |a: Colour, b: Colour, tolerance: f64| -> bool {
+        return (a.r - b.r).abs() < tolerance
+            && (a.g - b.g).abs() < tolerance
+            && (a.b - b.b).abs() < tolerance;
+    };
+
+    // The familiar sRGB matrix, to the precision it's usually quoted
+    let m = ColourSpace::LinearSrgb.rgb_to_xyz();
+    assert!((m[0][0] - 0.4124).abs() < 1e-4 && (m[1][1] - 0.7152).abs() < 1e-4);
+    assert!((m[2][2] - 0.9505).abs() < 1e-4);
+
+    // White stays white, and colours survive a round trip through every space
+    let white = Colour::new(1.0, 1.0, 1.0);
+    let orange = Colour::new(0.9, 0.4, 0.1);
+    let spaces = [
+        ColourSpace::LinearSrgb,
+        ColourSpace::AcesCg,
+        ColourSpace::Rec2020,
+        ColourSpace::DisplayP3,
+    ];
+    for from in spaces {
+        for to in spaces {
+            assert!(close(from.convert(white, to), white, 1e-9));
+            let there = from.convert(orange, to);
+            assert!(close(to.convert(there, from), orange, 1e-9));
+        }
+    }
+    // Pure sRGB red is inside the wider gamuts, so less saturated there
+    let red = ColourSpace::LinearSrgb.convert(Colour::new(1.0, 0.0, 0.0), ColourSpace::Rec2020);
+    assert!(red.r < 1.0 && red.g > 0.0 && red.b > 0.0);
+
+    for encoding in [Encoding::Linear, Encoding::Srgb, Encoding::Gamma(2.2)] {
+        for v in [0.0, 0.002, 0.2, 0.7, 1.0] {
+            assert!((encoding.decode(encoding.encode(v)) - v).abs() < 1e-12);
+        }
+    }
+    assert!((Encoding::Srgb.encode(0.5) - 0.7354).abs() < 1e-4);
+}
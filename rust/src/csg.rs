@@ -0,0 +1,173 @@
+use crate::hittable::{Hit, HitRecord, Interval};
+use crate::ray::Ray;
+
+// How two solids are combined, by whether a point is inside each of them
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[allow(dead_code)]
+pub enum Operation {
+    Union,
+    Intersection,
+    // Inside the first and outside the second, such as a hollow shell
+    Difference,
+}
+
+impl Operation {
+    fn inside(&self, a: bool, b: bool) -> bool {
+        return match self {
+            Operation::Union => a || b,
+            Operation::Intersection => a && b,
+            Operation::Difference => a && !b,
+        };
+    }
+}
+
+// Constructive solid geometry, such as a lens from two intersecting spheres. Both children
+// must be closed, reporting every span of a ray inside them through `intervals`, and each
+// surface of the result keeps the material of the child it came from.
+pub struct Csg {
+    operation: Operation,
+    a: Box<dyn Hit>,
+    b: Box<dyn Hit>,
+}
+
+impl Csg {
+    pub fn new(operation: Operation, a: impl Hit + 'static, b: impl Hit + 'static) -> Csg {
+        return Csg {
+            operation,
+            a: Box::new(a),
+            b: Box::new(b),
+        };
+    }
+}
+
+impl Hit for Csg {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        for interval in self.intervals(ray) {
+            for rec in [interval.enter, interval.exit] {
+                if rec.t >= t_min && rec.t <= t_max {
+                    return Some(rec);
+                }
+            }
+        }
+        return None;
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        // Every surface of either child along the ray, with which child it belongs to and
+        // whether the ray goes into it there
+        let mut events = Vec::new();
+        for (from_a, child) in [(true, &self.a), (false, &self.b)] {
+            for interval in child.intervals(ray) {
+                events.push((interval.enter, from_a, true));
+                events.push((interval.exit, from_a, false));
+            }
+        }
+        events.sort_by(|x, y| x.0.t.total_cmp(&y.0.t));
+
+        // Sweeps along the ray, keeping the surfaces where the result changes from outside to
+        // inside or back
+        let mut intervals = Vec::new();
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter: Option<HitRecord> = None;
+        for (mut rec, from_a, entering) in events {
+            let was_inside = self.operation.inside(in_a, in_b);
+            if from_a {
+                in_a = entering;
+            } else {
+                in_b = entering;
+            }
+            let inside = self.operation.inside(in_a, in_b);
+            if inside == was_inside {
+                continue;
+            }
+            // Surfaces of a subtracted child face the other way, so whether the ray is going
+            // into the result decides the side rather than the child
+            rec.front_face = inside;
+            if inside {
+                enter = Some(rec);
+            } else if let Some(enter) = enter.take() {
+                intervals.push(Interval { enter, exit: rec });
+            }
+        }
+        return intervals;
+    }
+}
+
+#[test]
+fn test_csg() {
+    use crate::colour::Colour;
+    use crate::mask::{AlphaMode, Masked};
+    use crate::material::{Diffuse, Material};
+    use crate::point::Point;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColour;
+    use crate::vector::Vector;
+    use std::sync::Arc;
+
+    let material: Arc<dyn Material> = Diffuse::new(Colour::new(0.5, 0.5, 0.5));
+    let sphere = |x: f64, radius: f64| -> Sphere {
+        return Sphere::new(Point::new(x, 0.0, 0.0), radius, material.clone());
+    };
+    let ray = Ray::new(Point::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+
+    // A lens, between the near side of the right sphere and the far side of the left one
+    let lens = Csg::new(Operation::Intersection, sphere(-1.4, 2.0), sphere(1.4, 2.0));
+    let intervals = lens.intervals(&ray);
+    assert_eq!(intervals.len(), 1);
+    assert!((intervals[0].enter.t - 4.4).abs() < 1e-9);
+    assert!((intervals[0].exit.t - 5.6).abs() < 1e-9);
+    let rec = lens.hit(&ray, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.t - 4.4).abs() < 1e-9 && rec.front_face);
+    assert!(rec.normal.dot(ray.direction) < 0.0);
+
+    // A hollow shell has two walls, and the ray leaves through the inner surface of the first
+    let shell = Csg::new(Operation::Difference, sphere(0.0, 1.0), sphere(0.0, 0.5));
+    let intervals = shell.intervals(&ray);
+    assert_eq!(intervals.len(), 2);
+    let spans: Vec<(f64, f64)> = intervals.iter().map(|i| (i.enter.t, i.exit.t)).collect();
+    assert_eq!(spans, vec![(4.0, 4.5), (5.5, 6.0)]);
+    for interval in &intervals {
+        assert!(interval.enter.front_face && !interval.exit.front_face);
+        assert!(interval.enter.normal.dot(ray.direction) < 0.0);
+        assert!(interval.exit.normal.dot(ray.direction) < 0.0);
+    }
+    // From inside the hollow, the first surface is the inner wall, entered
+    let inside = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+    let rec = shell.hit(&inside, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.t - 0.5).abs() < 1e-9 && rec.front_face);
+
+    // Overlapping spheres merge into one span, and disjoint ones stay apart
+    let union = Csg::new(Operation::Union, sphere(-0.5, 1.0), sphere(0.5, 1.0));
+    let spans: Vec<(f64, f64)> = union
+        .intervals(&ray)
+        .iter()
+        .map(|i| (i.enter.t, i.exit.t))
+        .collect();
+    assert_eq!(spans, vec![(3.5, 6.5)]);
+    let apart = Csg::new(Operation::Union, sphere(-2.0, 1.0), sphere(2.0, 1.0));
+    assert_eq!(apart.intervals(&ray).len(), 2);
+
+    // A masked operand only keeps the spans whose surfaces are both solid
+    let solid = Masked::new(
+        sphere(-2.0, 1.0),
+        SolidColour::scalar(1.0),
+        AlphaMode::Threshold(0.5),
+    );
+    let cut = Masked::new(
+        sphere(2.0, 1.0),
+        SolidColour::scalar(0.0),
+        AlphaMode::Threshold(0.5),
+    );
+    let spans: Vec<(f64, f64)> = Csg::new(Operation::Union, solid, cut)
+        .intervals(&ray)
+        .iter()
+        .map(|i| (i.enter.t, i.exit.t))
+        .collect();
+    assert_eq!(spans, vec![(2.0, 4.0)]);
+
+    // Results nest, and a ray that misses has nothing to hit
+    let nested = Csg::new(Operation::Difference, lens, sphere(0.0, 0.3));
+    assert_eq!(nested.intervals(&ray).len(), 2);
+    let miss = Ray::new(Point::new(-5.0, 3.0, 0.0), Vector::new(1.0, 0.0, 0.0));
+    assert!(nested.hit(&miss, 0.001, f64::INFINITY).is_none());
+}
@@ -0,0 +1,147 @@
+use crate::colour::Colour;
+use crate::filter::Filter;
+use crate::tile::Tile;
+
+#[derive(Clone, Copy)]
+struct FilmPixel {
+    sum: Colour,
+    weight: f64,
+}
+
+impl FilmPixel {
+    fn empty() -> FilmPixel {
+        return FilmPixel {
+            sum: Colour::new(0.0, 0.0, 0.0),
+            weight: 0.0,
+        };
+    }
+}
+
+pub struct Film {
+    pub width: u32,
+    pub height: u32,
+    pub filter: Filter,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32, filter: Filter) -> Film {
+        return Film {
+            width,
+            height,
+            filter,
+            pixels: vec![FilmPixel::empty(); (width * height) as usize],
+        };
+    }
+
+    pub fn tile(&self, tile: &Tile) -> FilmTile {
+        // Samples near the tile's edges spill into neighbouring pixels by up to the filter radius
+        let margin = (self.filter.radius() - 0.5).ceil().max(0.0) as u32;
+        let x0 = tile.x.saturating_sub(margin);
+        let y0 = tile.y.saturating_sub(margin);
+        let x1 = (tile.x + tile.width + margin).min(self.width);
+        let y1 = (tile.y + tile.height + margin).min(self.height);
+        return FilmTile {
+            x0,
+            y0,
+            width: x1 - x0,
+            height: y1 - y0,
+            filter: self.filter,
+            pixels: vec![FilmPixel::empty(); ((x1 - x0) * (y1 - y0)) as usize],
+        };
+    }
+
+    pub fn merge(&mut self, tile: FilmTile) {
+        for ty in 0..tile.height {
+            for tx in 0..tile.width {
+                let src = tile.pixels[(ty * tile.width + tx) as usize];
+                let dst = &mut self.pixels[((tile.y0 + ty) * self.width + tile.x0 + tx) as usize];
+                dst.sum += src.sum;
+                dst.weight += src.weight;
+            }
+        }
+    }
+
+    pub fn resolve(&self) -> Vec<Colour> {
+        return self
+            .pixels
+            .iter()
+            .map(|p| {
+                if p.weight.abs() < 1e-12 {
+                    return Colour::new(0.0, 0.0, 0.0);
+                }
+                // Negative filter lobes can ring below zero around bright edges
+                let c = p.sum * (1.0 / p.weight);
+                return Colour::new(c.r.max(0.0), c.g.max(0.0), c.b.max(0.0));
+            })
+            .collect();
+    }
+}
+
+pub struct FilmTile {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    height: u32,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+}
+
+impl FilmTile {
+    // Adds a sample at continuous image coordinates, where pixel (x, y) has its centre at (x + 0.5, y + 0.5)
+    pub fn add_sample(&mut self, px: f64, py: f64, colour: Colour) {
+        let radius = self.filter.radius();
+        let x_min = ((px - 0.5 - radius).ceil().max(self.x0 as f64)) as u32;
+        let y_min = ((py - 0.5 - radius).ceil().max(self.y0 as f64)) as u32;
+        let x_max = ((px - 0.5 + radius).floor() as i64).min((self.x0 + self.width) as i64 - 1);
+        let y_max = ((py - 0.5 + radius).floor() as i64).min((self.y0 + self.height) as i64 - 1);
+
+        for y in (y_min as i64)..=y_max {
+            for x in (x_min as i64)..=x_max {
+                let weight = self
+                    .filter
+                    .evaluate(x as f64 + 0.5 - px, y as f64 + 0.5 - py);
+                let idx = ((y as u32 - self.y0) * self.width + (x as u32 - self.x0)) as usize;
+                self.pixels[idx].sum += colour * weight;
+                self.pixels[idx].weight += weight;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_film() {
+    let tile = Tile {
+        x: 2,
+        y: 2,
+        width: 2,
+        height: 2,
+    };
+
+    // A half pixel box filter keeps every sample inside its own pixel
+    let mut film = Film::new(6, 6, Filter::Box { radius: 0.5 });
+    let mut film_tile = film.tile(&tile);
+    film_tile.add_sample(2.2, 3.9, Colour::new(1.0, 0.5, 0.0));
+    film_tile.add_sample(2.7, 3.1, Colour::new(0.0, 0.5, 1.0));
+    film.merge(film_tile);
+    let image = film.resolve();
+    let p = image[3 * 6 + 2];
+    assert_eq!((p.r, p.g, p.b), (0.5, 0.5, 0.5));
+    assert_eq!(image[3 * 6 + 3].g, 0.0);
+
+    // Wider filters splat across the tile's border into its neighbours
+    let mut film = Film::new(6, 6, Filter::Tent { radius: 1.5 });
+    let mut film_tile = film.tile(&tile);
+    film_tile.add_sample(2.5, 2.5, Colour::new(1.0, 1.0, 1.0));
+    film.merge(film_tile);
+    let image = film.resolve();
+    assert_eq!(image[6 + 1].r, 1.0);
+    assert_eq!(image[0].r, 0.0);
+    let mitchell = Filter::Mitchell {
+        radius: 2.0,
+        b: 1.0 / 3.0,
+        c: 1.0 / 3.0,
+    };
+    assert!(mitchell.evaluate(0.0, 0.0) > mitchell.evaluate(0.5, 0.0));
+    assert!(mitchell.evaluate(1.5, 0.0) < 0.0);
+}
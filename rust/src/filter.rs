@@ -0,0 +1,67 @@
+use std::f64::consts::PI;
+
+// Separable pixel reconstruction filters, evaluated on offsets measured in pixels
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Filter {
+    Box { radius: f64 },
+    Tent { radius: f64 },
+    Gaussian { radius: f64, alpha: f64 },
+    Mitchell { radius: f64, b: f64, c: f64 },
+    Lanczos { radius: f64 },
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        return match *self {
+            Filter::Box { radius } => radius,
+            Filter::Tent { radius } => radius,
+            Filter::Gaussian { radius, .. } => radius,
+            Filter::Mitchell { radius, .. } => radius,
+            Filter::Lanczos { radius } => radius,
+        };
+    }
+
+    pub fn evaluate(&self, dx: f64, dy: f64) -> f64 {
+        return self.evaluate_1d(dx) * self.evaluate_1d(dy);
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        if x > self.radius() {
+            return 0.0;
+        }
+        return match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => radius - x,
+            Filter::Gaussian { radius, alpha } => {
+                // Shifted down so the filter reaches zero at its radius
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.0)
+            }
+            Filter::Mitchell { radius, b, c } => mitchell_1d(2.0 * x / radius, b, c),
+            Filter::Lanczos { radius } => sinc(x) * sinc(x / radius),
+        };
+    }
+}
+
+fn mitchell_1d(x: f64, b: f64, c: f64) -> f64 {
+    // Cubic spline over [0, 2], as given by Mitchell and Netravali
+    if x > 1.0 {
+        return ((-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0;
+    }
+    return ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+        + (6.0 - 2.0 * b))
+        / 6.0;
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+    return (PI * x).sin() / (PI * x);
+}
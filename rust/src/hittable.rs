@@ -3,12 +3,22 @@ use std::sync::Arc;
 use crate::material::Material;
 use crate::point::Point;
 use crate::ray::Ray;
-use crate::vector::Vector;
+use crate::stats;
+use crate::vector::{Onb, Vector};
 
 pub struct HitRecord {
     pub p: Point,
+    // Shading normal, which materials light with, facing the incoming ray
     pub normal: Vector,
+    // The surface's true normal, on the same side as the shading normal
+    pub geometric_normal: Vector,
+    // Rates of change of the point with the surface coordinates, which give the tangent frame
+    pub dpdu: Vector,
+    pub dpdv: Vector,
     pub t: f64,
+    // Surface coordinates for texture lookups
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
     pub material: Arc<dyn Material>,
 }
@@ -21,10 +31,16 @@ impl HitRecord {
         ray: &Ray,
         material: Arc<dyn Material>,
     ) -> HitRecord {
+        let frame = Onb::from_w(normal);
         let mut rec = HitRecord {
             p,
             normal,
+            geometric_normal: normal,
+            dpdu: frame.u,
+            dpdv: frame.v,
             t,
+            u: 0.0,
+            v: 0.0,
             material,
             front_face: false,
         };
@@ -35,11 +51,50 @@ impl HitRecord {
     fn set_face_normal(&mut self, ray: &Ray, normal: Vector) {
         self.front_face = ray.direction.dot(normal) < 0.0;
         self.normal = if self.front_face { normal } else { -normal };
+        self.geometric_normal = self.normal;
+    }
+
+    // Replaces the shading normal, bending it towards the viewer when it would face away, as
+    // otherwise light would leak through surfaces seen at grazing angles
+    pub fn set_shading_normal(&mut self, ray: &Ray, normal: Vector) {
+        let wo = -ray.direction.unit();
+        let mut normal = normal.unit();
+        if normal.dot(self.geometric_normal) < 0.0 {
+            normal = -normal;
+        }
+        let cos_o = wo.dot(normal);
+        if cos_o < 0.01 {
+            normal = (normal + wo * (0.01 - cos_o)).unit();
+        }
+        self.normal = normal;
     }
 }
 
+// A span of a ray's line inside a closed object, between the surfaces it enters and leaves by
+pub struct Interval {
+    pub enter: HitRecord,
+    pub exit: HitRecord,
+}
+
 pub trait Hit: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+    // Every span of the ray's line inside the object, in order along it, including any behind
+    // the ray's origin. Only closed objects have an inside, so by default there are none, and
+    // other objects can't be combined by constructive solid geometry.
+    fn intervals(&self, _ray: &Ray) -> Vec<Interval> {
+        return Vec::new();
+    }
+}
+
+impl Hit for Box<dyn Hit> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        return (**self).hit(ray, t_min, t_max);
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        return (**self).intervals(ray);
+    }
 }
 
 pub struct Environment {
@@ -56,6 +111,7 @@ impl Hit for Environment {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
         let mut out: Option<HitRecord> = None;
         let mut closest_so_far = t_max;
+        stats::intersection_tests(self.hittables.len() as u64);
         for hittable in self.hittables.iter() {
             if let Some(temp_record) = hittable.hit(ray, t_min, closest_so_far) {
                 closest_so_far = temp_record.t;
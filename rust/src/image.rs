@@ -0,0 +1,264 @@
+use std::fs;
+use std::io;
+
+use crate::colour::Colour;
+use crate::colour_space::{apply, ColourSpace, Encoding};
+
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<Colour>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, pixels: Vec<Colour>) -> Image {
+        return Image {
+            width,
+            height,
+            pixels,
+        };
+    }
+
+    // Picks a reader from the file's extension
+    pub fn load(path: &str) -> io::Result<Image> {
+        let extension = path.rsplit('.').next().unwrap_or("").to_lowercase();
+        return match extension.as_str() {
+            "hdr" => Image::load_hdr(path),
+            "exr" => Image::load_exr(path),
+            _ => Image::load_ppm(path),
+        };
+    }
+
+    // Reads plain (P3) or binary (P6) PPM files, scaling values into [0, 1]
+    pub fn load_ppm(path: &str) -> io::Result<Image> {
+        let data = fs::read(path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        // The header is whitespace separated and may contain comments
+        let mut fields: Vec<String> = Vec::new();
+        let mut pos = 0;
+        while fields.len() < 4 && pos < data.len() {
+            if data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else if data[pos].is_ascii_whitespace() {
+                pos += 1;
+            } else {
+                let start = pos;
+                while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+                fields.push(String::from_utf8_lossy(&data[start..pos]).to_string());
+            }
+        }
+        if fields.len() < 4 {
+            return Err(invalid("Truncated PPM header."));
+        }
+        let parse = |s: &String| s.parse::<u32>().map_err(|_| invalid("Invalid PPM header."));
+        let (width, height, max) = (parse(&fields[1])?, parse(&fields[2])?, parse(&fields[3])?);
+        let count = (width * height * 3) as usize;
+
+        let values: Vec<u32> = match fields[0].as_str() {
+            "P3" => String::from_utf8_lossy(&data[pos..])
+                .split_ascii_whitespace()
+                .take(count)
+                .map(|v| v.parse::<u32>().map_err(|_| invalid("Invalid PPM value.")))
+                .collect::<io::Result<Vec<u32>>>()?,
+            "P6" => {
+                // A single whitespace byte separates the header from the raster
+                let raster = &data[(pos + 1).min(data.len())..];
+                if max < 256 {
+                    raster.iter().take(count).map(|&v| v as u32).collect()
+                } else {
+                    raster
+                        .chunks_exact(2)
+                        .take(count)
+                        .map(|v| ((v[0] as u32) << 8) | v[1] as u32)
+                        .collect()
+                }
+            }
+            _ => return Err(invalid("Unsupported PPM format.")),
+        };
+        if values.len() < count {
+            return Err(invalid("Truncated PPM data."));
+        }
+
+        let scale = 1.0 / max as f64;
+        let pixels = values
+            .chunks_exact(3)
+            .map(|v| {
+                Colour::new(
+                    v[0] as f64 * scale,
+                    v[1] as f64 * scale,
+                    v[2] as f64 * scale,
+                )
+            })
+            .collect();
+        return Ok(Image::new(width, height, pixels));
+    }
+
+    // Reads Radiance RGBE files, with either flat or run length encoded scanlines
+    pub fn load_hdr(path: &str) -> io::Result<Image> {
+        let data = fs::read(path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        // Text header, ended by a blank line and followed by the resolution line
+        let mut pos = 0;
+        let mut next_line = || -> io::Result<String> {
+            let start = pos;
+            while pos < data.len() && data[pos] != b'\n' {
+                pos += 1;
+            }
+            if pos >= data.len() {
+                return Err(invalid("Truncated HDR header."));
+            }
+            pos += 1;
+            return Ok(String::from_utf8_lossy(&data[start..pos - 1]).to_string());
+        };
+        if !next_line()?.starts_with("#?") {
+            return Err(invalid("Missing HDR signature."));
+        }
+        loop {
+            let line = next_line()?;
+            if line.trim().is_empty() {
+                break;
+            }
+            if line.starts_with("FORMAT=") && line.trim() != "FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid("Unsupported HDR format."));
+            }
+        }
+        let resolution: Vec<String> = next_line()?
+            .split_whitespace()
+            .map(|s| s.to_string())
+            .collect();
+        if resolution.len() != 4 || resolution[0] != "-Y" || resolution[2] != "+X" {
+            return Err(invalid("Unsupported HDR orientation."));
+        }
+        let parse = |s: &String| s.parse::<u32>().map_err(|_| invalid("Invalid HDR size."));
+        let (height, width) = (parse(&resolution[1])?, parse(&resolution[3])?);
+
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        let mut scanline = vec![[0u8; 4]; width as usize];
+        for _ in 0..height {
+            let rle = (8..32768).contains(&width)
+                && pos + 4 <= data.len()
+                && data[pos] == 2
+                && data[pos + 1] == 2
+                && ((data[pos + 2] as u32) << 8 | data[pos + 3] as u32) == width;
+            if rle {
+                // Each of the four components is run length encoded separately
+                pos += 4;
+                for c in 0..4 {
+                    let mut x = 0;
+                    while x < width as usize {
+                        let count = *data
+                            .get(pos)
+                            .ok_or_else(|| invalid("Truncated HDR data."))?;
+                        pos += 1;
+                        if count > 128 {
+                            let run = (count - 128) as usize;
+                            let value = *data
+                                .get(pos)
+                                .ok_or_else(|| invalid("Truncated HDR data."))?;
+                            pos += 1;
+                            for px in scanline.iter_mut().skip(x).take(run) {
+                                px[c] = value;
+                            }
+                            x += run;
+                        } else {
+                            for _ in 0..count {
+                                let value = *data
+                                    .get(pos)
+                                    .ok_or_else(|| invalid("Truncated HDR data."))?;
+                                scanline[x.min(width as usize - 1)][c] = value;
+                                pos += 1;
+                                x += 1;
+                            }
+                        }
+                    }
+                }
+            } else {
+                for px in scanline.iter_mut() {
+                    let bytes = data
+                        .get(pos..pos + 4)
+                        .ok_or_else(|| invalid("Truncated HDR data."))?;
+                    px.copy_from_slice(bytes);
+                    pos += 4;
+                }
+            }
+
+            for rgbe in scanline.iter() {
+                if rgbe[3] == 0 {
+                    pixels.push(Colour::new(0.0, 0.0, 0.0));
+                } else {
+                    let scale = 2f64.powi(rgbe[3] as i32 - 136);
+                    pixels.push(Colour::new(
+                        rgbe[0] as f64 * scale,
+                        rgbe[1] as f64 * scale,
+                        rgbe[2] as f64 * scale,
+                    ));
+                }
+            }
+        }
+        return Ok(Image::new(width, height, pixels));
+    }
+
+    // Reads the first RGB(A) layer of an OpenEXR file
+    pub fn load_exr(path: &str) -> io::Result<Image> {
+        let image = exr::prelude::read_first_rgba_layer_from_file(
+            path,
+            |resolution, _| {
+                let (width, height) = (resolution.width(), resolution.height());
+                Image::new(
+                    width as u32,
+                    height as u32,
+                    vec![Colour::new(0.0, 0.0, 0.0); width * height],
+                )
+            },
+            |image: &mut Image, position, (r, g, b, _): (f32, f32, f32, f32)| {
+                let idx = position.y() * image.width as usize + position.x();
+                image.pixels[idx] = Colour::new(r as f64, g as f64, b as f64);
+            },
+        )
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        return Ok(image.layer_data.channel_data.pixels);
+    }
+
+    // Writes pixels that have already been encoded for output
+    pub fn to_ppm(&self) -> String {
+        let mut out = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for pixel in self.pixels.iter() {
+            out.push_str(&format!("{}\n", pixel.render_encoded())[..]);
+        }
+        return out;
+    }
+
+    // Undoes the transfer function a colour image was stored with, and brings it from its
+    // colour space into the working one. Loaders leave values as stored, since images holding
+    // data, such as normal maps, shouldn't be transformed.
+    pub fn into_working(
+        mut self,
+        encoding: Encoding,
+        space: ColourSpace,
+        working: ColourSpace,
+    ) -> Image {
+        let m = space.matrix_to(working);
+        for pixel in self.pixels.iter_mut() {
+            let linear = encoding.decode_colour(*pixel);
+            *pixel = if space == working {
+                linear
+            } else {
+                apply(m, linear)
+            };
+        }
+        return self;
+    }
+
+    // (0, 0) is the top left pixel
+    pub fn get(&self, x: u32, y: u32) -> Colour {
+        let x = x.min(self.width - 1);
+        let y = y.min(self.height - 1);
+        return self.pixels[(y * self.width + x) as usize];
+    }
+}
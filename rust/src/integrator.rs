@@ -0,0 +1,535 @@
+use rand::Rng;
+
+use crate::colour::Colour;
+use crate::colour_space::{apply, ColourSpace};
+use crate::hittable::{Hit, HitRecord};
+use crate::medium::Medium;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::scene::Scene;
+use crate::spectrum::{
+    sample_wavelength, sample_wavelengths, samples_to_rgb, wavelength_rgb, SigmoidSpectrum,
+};
+use crate::stats;
+use crate::vector::Vector;
+
+// Scattering events a random walk may take before the path is given up on, which only the
+// palest and thickest media reach
+const MAX_WALK_STEPS: usize = 1024;
+
+// Weights a strategy's sample against another's, by the power heuristic with an exponent of two
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        return 0.0;
+    }
+    return a / (a + b);
+}
+
+// Fraction of light reaching the point from the given direction and distance. Shadow rays
+// pass through the boundaries of volumes, and fade through the media along the way, starting
+// with the ones given, while any other surface blocks them.
+fn transmittance(
+    scene: &Scene,
+    media: &[Medium],
+    p: Point,
+    direction: Vector,
+    distance: f64,
+    lift: &dyn Fn(Colour) -> Colour,
+) -> Colour {
+    stats::shadow_ray();
+    let shadow = Ray::new(p, direction);
+    let t_max = distance * (1.0 - 1e-6);
+    let mut media = media.to_vec();
+    let mut tr = Colour::new(1.0, 1.0, 1.0);
+    let mut t = 0.0;
+    loop {
+        let rec = scene.world.hit(&shadow, t + 0.001, t_max);
+        let end = rec.as_ref().map_or(t_max, |rec| rec.t);
+        if let Some(medium) = media.last() {
+            tr = tr * segment_transmittance(medium, &shadow, t, end, lift);
+        }
+        let rec = match rec {
+            Some(rec) => rec,
+            None => return tr,
+        };
+        if !rec.material.invisible() || tr.r.max(tr.g).max(tr.b) <= 0.0 {
+            return Colour::new(0.0, 0.0, 0.0);
+        }
+        cross(&mut media, &rec, direction);
+        t = rec.t;
+    }
+}
+
+// Transmittance through a medium between two distances along the ray. Through a density, it's
+// estimated by ratio tracking, which steps through the medium as if it were as dense as it gets
+// everywhere, and scales by the fraction of that density that's missing at each step.
+fn segment_transmittance(
+    medium: &Medium,
+    ray: &Ray,
+    t0: f64,
+    t1: f64,
+    lift: &dyn Fn(Colour) -> Colour,
+) -> Colour {
+    let length = ray.direction.length();
+    let distance = (t1 - t0) * length;
+    let mut tr = Colour::new(1.0, 1.0, 1.0);
+    if !distance.is_finite() {
+        return tr;
+    }
+    if medium.density.is_none() {
+        // Extinction is lifted rather than the transmittance, which changes with distance
+        return Medium::new(lift(medium.extinction())).transmittance(distance);
+    }
+    let sigma_t = lift(medium.extinction());
+    let majorant = medium.max_density() * sigma_t.r.max(sigma_t.g).max(sigma_t.b);
+    if majorant <= 0.0 {
+        return tr;
+    }
+    let mut rng = rand::thread_rng();
+    let mut s = 0.0;
+    loop {
+        s -= (1.0 - rng.gen::<f64>()).ln() / majorant;
+        if s >= distance {
+            return tr;
+        }
+        let d = medium.density_at(ray.at(t0 + s / length));
+        let missing = |sigma: f64| -> f64 { (1.0 - d * sigma / majorant).max(0.0) };
+        tr = tr * Colour::new(missing(sigma_t.r), missing(sigma_t.g), missing(sigma_t.b));
+        // Russian roulette, rather than stepping on through what little light is left
+        if tr.r.max(tr.g).max(tr.b) < 0.1 {
+            if rng.gen::<f64>() < 0.5 {
+                return Colour::new(0.0, 0.0, 0.0);
+            }
+            tr = tr * 2.0;
+        }
+    }
+}
+
+// Enters or leaves the surface's medium, for a ray crossing it in the given direction
+fn cross(media: &mut Vec<Medium>, rec: &HitRecord, direction: Vector) {
+    if direction.dot(rec.geometric_normal) < 0.0 {
+        if let Some(medium) = rec.material.medium() {
+            if rec.front_face {
+                media.push(medium);
+            } else {
+                media.pop();
+            }
+        }
+    }
+}
+
+// Average over the channels being followed, which after a path keeps to the hero wavelength
+// is only the first
+fn mean(colour: Colour, channels: usize) -> f64 {
+    return [colour.r, colour.g, colour.b][..channels]
+        .iter()
+        .sum::<f64>()
+        / channels as f64;
+}
+
+// Checks a direction is on the same side of the shading normal as the geometric one. Where a
+// perturbed normal disagrees, light would leak through the surface, so the direction is dropped.
+fn same_side(rec: &HitRecord, direction: Vector) -> bool {
+    return direction.dot(rec.normal) * direction.dot(rec.geometric_normal) > 0.0;
+}
+
+// Path traces the radiance arriving along the ray. The background is reached both by
+// scattered rays and, where it can be importance sampled, by shadow rays from each surface,
+// with the two combined by multiple importance sampling. Punctual lights are only reached
+// by shadow rays. Transmitting into or out of a material with a medium pushes or pops it, and
+// the innermost medium absorbs light along each segment of the path, or, if it scatters, sends
+// the path on a random walk through it. On meeting a dispersive material, the path picks a
+// single wavelength to follow from then on, weighted by its colour.
+pub fn ray_colour(ray: &Ray, scene: &Scene, max_depth: i32) -> Colour {
+    return trace(ray, scene, max_depth, None);
+}
+
+// Path traces the radiance arriving along the ray at three wavelengths, spread from a hero
+// wavelength, and converts it to colour. Every colour met along the way is taken to sRGB's
+// primaries, where the spectra are fitted, and upsampled to a spectrum. Where scattering
+// depends on the wavelength in ways colours can't describe, the path carries on with the hero
+// wavelength alone.
+pub fn ray_colour_spectral(ray: &Ray, scene: &Scene, max_depth: i32) -> Colour {
+    let wavelengths = sample_wavelengths(rand::thread_rng().gen());
+    let ray = Ray {
+        wavelength: Some(wavelengths[0]),
+        ..*ray
+    };
+    let values = trace(&ray, scene, max_depth, Some(wavelengths));
+    let colour = samples_to_rgb(wavelengths, [values.r, values.g, values.b]);
+    return ColourSpace::LinearSrgb.convert(colour, scene.space);
+}
+
+// With wavelengths given, the colours carried along the path hold the values at each of them
+// in place of their red, green and blue channels
+fn trace(ray: &Ray, scene: &Scene, max_depth: i32, wavelengths: Option<[f64; 3]>) -> Colour {
+    let lift = |colour: Colour| -> Colour {
+        return match wavelengths {
+            Some([a, b, c]) => {
+                let spectrum = SigmoidSpectrum::cached(apply(scene.to_srgb, colour));
+                Colour::new(
+                    spectrum.evaluate(a),
+                    spectrum.evaluate(b),
+                    spectrum.evaluate(c),
+                )
+            }
+            None => colour,
+        };
+    };
+    // A BSDF's value over its pdf is what scattering weights the path by, and for plain
+    // materials it's their constant colour. Lifting that, rather than the value itself, reuses
+    // its fit, and gives light sampling the same spectrum as scattering.
+    let lift_bsdf = |f: Colour, pdf: f64| -> Colour {
+        if pdf > 0.0 {
+            return lift(f * (1.0 / pdf)) * pdf;
+        }
+        return lift(f);
+    };
+    let mut hero_only = false;
+    let mut radiance = Colour::new(0.0, 0.0, 0.0);
+    let mut throughput = Colour::new(1.0, 1.0, 1.0);
+    let mut ray = *ray;
+    // Density of the last scattered direction, or None after a specular bounce
+    let mut scatter_pdf: Option<f64> = None;
+    let mut media: Vec<Medium> = Vec::new();
+    // Distances through scattering media are all sampled by one channel's extinction, and
+    // weighted by the average density of the path so far over every channel, which keeps
+    // channels unlike the chosen one from being swamped by noise. Densities are relative to
+    // the chosen channel's.
+    let mut chosen = rand::thread_rng().gen_range(0..3);
+    let mut densities = Colour::new(1.0, 1.0, 1.0);
+    let mut channels = 3;
+
+    for _ in 0..max_depth {
+        stats::ray();
+        let mut hit = scene.world.hit(&ray, 0.001, f64::INFINITY);
+        // Walk through a scattering, emitting or varying medium, until the path reaches a
+        // surface. In homogeneous media, which are usually the insides of solid objects, each
+        // step goes straight to the next scattering event. Through a density, steps are taken
+        // as if the medium were as dense as it gets everywhere, by delta tracking, and most
+        // are null collisions that carry straight on. Such media are usually volumes light can
+        // reach inside, so are light sampled at each scattering event.
+        let mut steps = 0;
+        while let Some(medium) = media.last().filter(|medium| !medium.absorbs_only()) {
+            // A ray that hits nothing has left the medium, however it slipped out, as it
+            // can by grazing a corner
+            let distance = match &hit {
+                Some(rec) => rec.t * ray.direction.length(),
+                None => break,
+            };
+            let length = ray.direction.length();
+            let tracked = medium.density.is_some() || medium.emits();
+            let mut rng = rand::thread_rng();
+            let scattered_at = if tracked {
+                let sigma_a = lift(medium.absorption);
+                let sigma_s = lift(medium.scattering);
+                let emission = lift(medium.emission);
+                let sigma_t = sigma_a + sigma_s;
+                let strongest = [sigma_t.r, sigma_t.g, sigma_t.b][..channels]
+                    .iter()
+                    .fold(0.0_f64, |a, &b| a.max(b));
+                let mut majorant = medium.max_density() * strongest;
+                // Emission is gathered at each step, so it needs them even where nothing absorbs
+                if medium.emits() {
+                    majorant = majorant.max(medium.max_density());
+                }
+                let mut t = 0.0;
+                loop {
+                    if majorant <= 0.0 {
+                        break None;
+                    }
+                    t -= (1.0 - rng.gen::<f64>()).ln() / majorant;
+                    if t >= distance {
+                        break None;
+                    }
+                    let d = medium.density_at(ray.at(t / length));
+                    radiance += throughput * emission * (d / majorant);
+                    let (absorbed, scattered) = (sigma_a * d, sigma_s * d);
+                    let null = |a: f64, s: f64| -> f64 { (majorant - a - s).max(0.0) };
+                    let null = Colour::new(
+                        null(absorbed.r, scattered.r),
+                        null(absorbed.g, scattered.g),
+                        null(absorbed.b, scattered.b),
+                    );
+                    // Picks an event by the average of its coefficient over the channels, and
+                    // weights each channel by how its own coefficient differs
+                    let (pa, ps, pn) = (
+                        mean(absorbed, channels),
+                        mean(scattered, channels),
+                        mean(null, channels),
+                    );
+                    let u = rng.gen::<f64>() * (pa + ps + pn);
+                    if u < pa {
+                        return radiance;
+                    }
+                    if u < pa + ps {
+                        throughput = throughput * scattered * (1.0 / ps);
+                        break Some(t);
+                    }
+                    throughput = throughput * null * (1.0 / pn);
+                }
+            } else {
+                let sigma_t = lift(medium.extinction());
+                let t = -(1.0 - rng.gen::<f64>()).ln() / [sigma_t.r, sigma_t.g, sigma_t.b][chosen];
+                let reached = t >= distance;
+                let t = t.min(distance);
+                let tr = Colour::new(
+                    (-sigma_t.r * t).exp(),
+                    (-sigma_t.g * t).exp(),
+                    (-sigma_t.b * t).exp(),
+                );
+                // Density of scattering here, or of getting as far as the surface
+                let pdfs = if reached { tr } else { sigma_t * tr };
+                let before = mean(densities, channels);
+                densities = densities * pdfs * (1.0 / [pdfs.r, pdfs.g, pdfs.b][chosen]);
+                let weight =
+                    before / (mean(densities, channels) * [pdfs.r, pdfs.g, pdfs.b][chosen]);
+                if reached {
+                    throughput = throughput * tr * weight;
+                    None
+                } else {
+                    throughput = throughput * lift(medium.albedo()) * sigma_t * tr * weight;
+                    Some(t)
+                }
+            };
+            let t = match scattered_at {
+                Some(t) => t,
+                None => break,
+            };
+            steps += 1;
+            if steps > MAX_WALK_STEPS {
+                return radiance;
+            }
+            let p = ray.at(t / length);
+
+            scatter_pdf = None;
+            if tracked {
+                if let Some((direction, light, light_pdf)) = scene.background.sample() {
+                    let phase = medium.phase(ray.direction, direction);
+                    let tr = transmittance(scene, &media, p, direction, f64::INFINITY, &lift);
+                    let weight = power_heuristic(light_pdf, phase);
+                    radiance += throughput * tr * lift(light) * (phase * weight / light_pdf);
+                }
+                for light in scene.lights.iter() {
+                    if let Some((direction, irradiance, distance)) = light.sample(p) {
+                        let phase = medium.phase(ray.direction, direction);
+                        let tr = transmittance(scene, &media, p, direction, distance, &lift);
+                        radiance += throughput * tr * lift(irradiance) * phase;
+                    }
+                }
+            }
+            let direction = medium.sample_phase(ray.direction, rng.gen(), rng.gen());
+            if tracked {
+                scatter_pdf = Some(medium.phase(ray.direction, direction));
+            }
+            ray = Ray {
+                origin: p,
+                direction,
+                ..ray
+            };
+            stats::ray();
+            hit = scene.world.hit(&ray, 0.0, f64::INFINITY);
+        }
+        let rec = match hit {
+            Some(rec) => rec,
+            None => {
+                let weight = match scatter_pdf {
+                    Some(pdf) => power_heuristic(pdf, scene.background.pdf(ray.direction)),
+                    None => 1.0,
+                };
+                radiance += throughput * lift(scene.background.colour(ray.direction)) * weight;
+                break;
+            }
+        };
+        stats::scatter(rec.material.name());
+        if let Some(medium) = media.last().filter(|medium| medium.absorbs_only()) {
+            // As through any homogeneous medium, absorption is lifted rather than the transmittance
+            let lifted = Medium::new(lift(medium.absorption));
+            throughput = throughput * lifted.transmittance(rec.t * ray.direction.length());
+        }
+        // The boundaries of volumes are only crossed
+        if rec.material.invisible() {
+            cross(&mut media, &rec, ray.direction);
+            ray = Ray {
+                origin: rec.p,
+                ..ray
+            };
+            continue;
+        }
+        // Emissive surfaces aren't light sampled, so are only found by scattered rays
+        radiance += throughput * lift(rec.material.emitted(&rec));
+        match wavelengths {
+            Some(_) if !hero_only && (rec.material.dispersive() || rec.material.iridescent()) => {
+                throughput = Colour::new(throughput.r * 3.0, 0.0, 0.0);
+                hero_only = true;
+                // Only the hero's density counts from here on
+                chosen = 0;
+                densities = Colour::new(1.0, 1.0, 1.0);
+                channels = 1;
+            }
+            None if ray.wavelength.is_none() && rec.material.dispersive() => {
+                let wavelength = sample_wavelength(rand::thread_rng().gen());
+                let weight = wavelength_rgb(wavelength);
+                throughput = throughput * ColourSpace::LinearSrgb.convert(weight, scene.space);
+                ray.wavelength = Some(wavelength);
+            }
+            _ => {}
+        }
+
+        // Next event estimation towards the background, through whichever media are on the
+        // side of the surface the light's on
+        let beyond = |direction: Vector| -> Vec<Medium> {
+            let mut beyond = media.clone();
+            cross(&mut beyond, &rec, direction);
+            return beyond;
+        };
+        if let Some((direction, light, light_pdf)) = scene.background.sample() {
+            let f = rec.material.eval(&ray, &rec, direction);
+            if f.luminance() > 0.0 && same_side(&rec, direction) {
+                let tr = transmittance(
+                    scene,
+                    &beyond(direction),
+                    rec.p,
+                    direction,
+                    f64::INFINITY,
+                    &lift,
+                );
+                let pdf = rec.material.pdf(&ray, &rec, direction);
+                let weight = power_heuristic(light_pdf, pdf);
+                radiance +=
+                    throughput * tr * lift_bsdf(f, pdf) * lift(light) * (weight / light_pdf);
+            }
+        }
+        for light in scene.lights.iter() {
+            if let Some((direction, irradiance, distance)) = light.sample(rec.p) {
+                let f = rec.material.eval(&ray, &rec, direction);
+                if f.luminance() > 0.0 && same_side(&rec, direction) {
+                    let tr =
+                        transmittance(scene, &beyond(direction), rec.p, direction, distance, &lift);
+                    let pdf = rec.material.pdf(&ray, &rec, direction);
+                    radiance += throughput * tr * lift_bsdf(f, pdf) * lift(irradiance);
+                }
+            }
+        }
+
+        match rec.material.scatter(&ray, &rec) {
+            Some((scattered, attenuation)) => {
+                if !same_side(&rec, scattered.direction) {
+                    break;
+                }
+                let pdf = rec.material.pdf(&ray, &rec, scattered.direction);
+                scatter_pdf = if pdf > 0.0 { Some(pdf) } else { None };
+                throughput = throughput * lift(attenuation);
+                cross(&mut media, &rec, scattered.direction);
+                ray = Ray {
+                    wavelength: ray.wavelength,
+                    ..scattered
+                };
+            }
+            None => break,
+        }
+    }
+    return radiance;
+}
+
+#[test]
+fn test_subsurface() {
+    use crate::background::Gradient;
+    use crate::hittable::Environment;
+    use crate::material::{RoughGlass, Subsurface};
+    use crate::microfacet::Ggx;
+    use crate::sphere::Sphere;
+    use std::sync::Arc;
+
+    // A furnace test: under an even white sky, a sphere that absorbs nothing looks white
+    // however differently each channel scatters inside it. Its surface is close to smooth, as
+    // rough ones lose some light to the microfacets shadowing each other.
+    let material = Arc::new(Subsurface {
+        boundary: RoughGlass {
+            refractive_idx: 1.4,
+            distribution: Ggx::new(0.01, 0.01),
+            interior: None,
+        },
+        interior: Medium {
+            scattering: Colour::new(2.5, 6.0, 20.0),
+            anisotropy: 0.3,
+            ..Medium::new(Colour::new(0.0, 0.0, 0.0))
+        },
+    });
+    let mut world = Environment { hittables: vec![] };
+    world.add(Sphere::new(Point::new(0.0, 0.0, 0.0), 1.0, material));
+    let white = Colour::new(1.0, 1.0, 1.0);
+    let background = Arc::new(Gradient {
+        start: white,
+        end: white,
+    });
+    let scene = Scene::new(world, background, vec![], ColourSpace::LinearSrgb);
+    let n = 10000;
+    let mut total = Colour::new(0.0, 0.0, 0.0);
+    for i in 0..n {
+        let x = 0.9 * (i as f64 / n as f64) - 0.45;
+        let ray = Ray::new(Point::new(x, 0.1, 5.0), Vector::new(0.0, 0.0, -1.0));
+        total += ray_colour(&ray, &scene, 1000);
+    }
+    let mean = total * (1.0 / n as f64);
+    assert!((mean.r - 1.0).abs() < 0.05 && (mean.g - 1.0).abs() < 0.05);
+    assert!((mean.b - 1.0).abs() < 0.05);
+}
+
+#[test]
+fn test_volumes() {
+    use crate::background::Gradient;
+    use crate::hittable::Environment;
+    use crate::volume::{Noise, Volume};
+    use std::sync::Arc;
+
+    let white = Colour::new(1.0, 1.0, 1.0);
+    let scene = |volume: Volume| -> Scene {
+        let mut world = Environment { hittables: vec![] };
+        world.add(volume);
+        let background = Arc::new(Gradient {
+            start: white,
+            end: white,
+        });
+        return Scene::new(world, background, vec![], ColourSpace::LinearSrgb);
+    };
+    let (min, max) = (Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+    let n = 10000;
+    let render = |scene: &Scene| -> Colour {
+        let mut total = Colour::new(0.0, 0.0, 0.0);
+        for i in 0..n {
+            let x = 0.9 * (i as f64 / n as f64) - 0.45;
+            let ray = Ray::new(Point::new(x, 0.1, 5.0), Vector::new(0.0, 0.0, -1.0));
+            total += ray_colour(&ray, scene, 1000);
+        }
+        return total * (1.0 / n as f64);
+    };
+
+    // A cloud that absorbs nothing is invisible under an even white sky
+    let cloud = Noise::new(Point::new(0.0, 0.0, 0.0), 1.0, 2.0, 4, 3);
+    let medium = Medium::heterogeneous(
+        cloud,
+        Colour::new(0.0, 0.0, 0.0),
+        Colour::new(4.0, 6.0, 8.0),
+    );
+    let mean = render(&scene(Volume::new(min, max, medium)));
+    assert!((mean.r - 1.0).abs() < 0.03 && (mean.g - 1.0).abs() < 0.03);
+    assert!((mean.b - 1.0).abs() < 0.03);
+
+    // Emission adds up along the path through the volume, dimmed by what's absorbed
+    let glow = Medium {
+        emission: Colour::new(0.5, 0.25, 0.0),
+        ..Medium::new(Colour::new(0.0, 0.0, 0.0))
+    };
+    let mean = render(&scene(Volume::new(min, max, glow)));
+    assert!((mean.r - 2.0).abs() < 0.05 && (mean.g - 1.5).abs() < 0.05);
+    assert!((mean.b - 1.0).abs() < 0.05);
+    let smoulder = Medium {
+        emission: Colour::new(2.0, 2.0, 2.0),
+        ..Medium::new(Colour::new(1.0, 1.0, 1.0))
+    };
+    // Through two units of absorption, (1 - e^-2) of the emission and e^-2 of the sky
+    let mean = render(&scene(Volume::new(min, max, smoulder)));
+    assert!((mean.r - (2.0 - (-2.0_f64).exp())).abs() < 0.05);
+}
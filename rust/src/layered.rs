@@ -0,0 +1,285 @@
+use rand::Rng;
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::medium::Medium;
+use crate::microfacet::{fresnel_dielectric, reflect, Ggx};
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vector::{Onb, Vector};
+
+// Materials built from others. Where a component is specular, and so can't be evaluated in a
+// given direction, the combination isn't light sampled there either, and scattered rays carry
+// the component's own weight instead.
+
+// Chooses between two materials by a weight read from a texture's red channel, where zero
+// gives the first material and one the second
+pub struct Mix {
+    pub first: Arc<dyn Material>,
+    pub second: Arc<dyn Material>,
+    pub weight: Arc<dyn Texture>,
+}
+
+impl Mix {
+    pub fn new(
+        first: Arc<dyn Material>,
+        second: Arc<dyn Material>,
+        weight: Arc<dyn Texture>,
+    ) -> Arc<Mix> {
+        return Arc::new(Mix {
+            first,
+            second,
+            weight,
+        });
+    }
+
+    fn weight(&self, record: &HitRecord) -> f64 {
+        return self
+            .weight
+            .value(record.u, record.v, record.p)
+            .r
+            .clamp(0.0, 1.0);
+    }
+
+    fn evaluate(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> (Colour, f64) {
+        let t = self.weight(record);
+        let pdfs = (
+            self.first.pdf(ray, record, direction),
+            self.second.pdf(ray, record, direction),
+        );
+        if (pdfs.0 == 0.0 && t < 1.0) || (pdfs.1 == 0.0 && t > 0.0) {
+            return (Colour::new(0.0, 0.0, 0.0), 0.0);
+        }
+        let value = self.first.eval(ray, record, direction) * (1.0 - t)
+            + self.second.eval(ray, record, direction) * t;
+        return (value, pdfs.0 * (1.0 - t) + pdfs.1 * t);
+    }
+}
+
+impl Material for Mix {
+    fn name(&self) -> &'static str {
+        return "mix";
+    }
+
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
+        let chosen = if rand::thread_rng().gen::<f64>() < self.weight(record) {
+            &self.second
+        } else {
+            &self.first
+        };
+        let (scattered, weight) = chosen.scatter(ray, record)?;
+        let (value, pdf) = self.evaluate(ray, record, scattered.direction);
+        if pdf > 0.0 {
+            return Some((scattered, value * (1.0 / pdf)));
+        }
+        return Some((scattered, weight));
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> Colour {
+        return self.evaluate(ray, record, direction).0;
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> f64 {
+        return self.evaluate(ray, record, direction).1;
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Colour {
+        let t = self.weight(record);
+        return self.first.emitted(record) * (1.0 - t) + self.second.emitted(record) * t;
+    }
+
+    fn dispersive(&self) -> bool {
+        return self.first.dispersive() || self.second.dispersive();
+    }
+
+    fn iridescent(&self) -> bool {
+        return self.first.iridescent() || self.second.iridescent();
+    }
+}
+
+// A thin dielectric clear coat over any base material. Light reaching the base is reduced by
+// the coat's Fresnel transmittance on the way in and out, and tinted by its colour, which is
+// what's left after a round trip through it at normal incidence.
+pub struct Coated {
+    pub base: Arc<dyn Material>,
+    pub refractive_idx: f64,
+    pub distribution: Ggx,
+    pub colour: Colour,
+}
+
+impl Coated {
+    pub fn new(
+        base: Arc<dyn Material>,
+        refractive_idx: f64,
+        roughness: f64,
+        colour: Colour,
+    ) -> Arc<Coated> {
+        return Arc::new(Coated {
+            base,
+            refractive_idx,
+            distribution: Ggx::new(roughness, roughness),
+            colour,
+        });
+    }
+
+    // Fraction of light passing down through the coat and back out again
+    fn transfer(&self, cos_o: f64, cos_i: f64) -> Colour {
+        let fresnel = (1.0 - fresnel_dielectric(cos_o, self.refractive_idx))
+            * (1.0 - fresnel_dielectric(cos_i, self.refractive_idx));
+        let path = 0.5 * (1.0 / cos_o + 1.0 / cos_i);
+        return Colour::new(
+            self.colour.r.powf(path),
+            self.colour.g.powf(path),
+            self.colour.b.powf(path),
+        ) * fresnel;
+    }
+
+    // Chance of sampling the coat rather than the base
+    fn coat_probability(&self, cos_o: f64) -> f64 {
+        return fresnel_dielectric(cos_o, self.refractive_idx).clamp(0.1, 0.9);
+    }
+
+    fn evaluate(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> (Colour, f64) {
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let frame = Onb::from_w(record.normal);
+        let wo = frame.to_local(-ray.direction.unit());
+        let wi = frame.to_local(direction.unit());
+        let base_pdf = self.base.pdf(ray, record, direction);
+        if wo.z() <= 0.0 || wi.z() <= 0.0 || base_pdf == 0.0 {
+            return (black, 0.0);
+        }
+        let h = (wo + wi).unit();
+        let f = fresnel_dielectric(wo.dot(h), self.refractive_idx);
+        let coat = f * self.distribution.d(h) * self.distribution.g(wo, wi) / (4.0 * wo.z());
+        let base = self.base.eval(ray, record, direction) * self.transfer(wo.z(), wi.z());
+        let p = self.coat_probability(wo.z());
+        let pdf =
+            p * self.distribution.pdf_visible(wo, h) / (4.0 * wo.dot(h)) + (1.0 - p) * base_pdf;
+        return (Colour::new(coat, coat, coat) + base, pdf);
+    }
+}
+
+impl Material for Coated {
+    fn name(&self) -> &'static str {
+        return "coated";
+    }
+
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
+        // Rays inside a transmitting base have already passed through the coat
+        if !record.front_face {
+            return self.base.scatter(ray, record);
+        }
+        let frame = Onb::from_w(record.normal);
+        let wo = frame.to_local(-ray.direction.unit());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let mut rng = rand::thread_rng();
+        let p = self.coat_probability(wo.z());
+        let coat_chosen = rng.gen::<f64>() < p;
+        let (scattered, weight) = if coat_chosen {
+            let m = self.distribution.sample_visible(wo, rng.gen(), rng.gen());
+            let wi = reflect(wo, m);
+            if wi.z() <= 0.0 {
+                return None;
+            }
+            let f = fresnel_dielectric(wo.dot(m), self.refractive_idx);
+            let weight = f * self.distribution.g(wo, wi) / self.distribution.g1(wo);
+            (
+                Ray::new(record.p, frame.to_world(wi)),
+                Colour::new(weight, weight, weight) * (1.0 / p),
+            )
+        } else {
+            let (scattered, weight) = self.base.scatter(ray, record)?;
+            let cos_i = frame.to_local(scattered.direction.unit()).z();
+            let transfer = if cos_i > 0.0 {
+                self.transfer(wo.z(), cos_i)
+            } else {
+                // Transmitted into the base, so the coat is only crossed once
+                Colour::new(1.0, 1.0, 1.0) * (1.0 - fresnel_dielectric(wo.z(), self.refractive_idx))
+            };
+            (scattered, weight * transfer * (1.0 / (1.0 - p)))
+        };
+
+        let (value, pdf) = self.evaluate(ray, record, scattered.direction);
+        if pdf > 0.0 {
+            return Some((scattered, value * (1.0 / pdf)));
+        }
+        return Some((scattered, weight));
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> Colour {
+        if !record.front_face {
+            return self.base.eval(ray, record, direction);
+        }
+        return self.evaluate(ray, record, direction).0;
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> f64 {
+        if !record.front_face {
+            return self.base.pdf(ray, record, direction);
+        }
+        return self.evaluate(ray, record, direction).1;
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        return self.base.medium();
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Colour {
+        return self.base.emitted(record);
+    }
+
+    fn dispersive(&self) -> bool {
+        return self.base.dispersive();
+    }
+
+    fn iridescent(&self) -> bool {
+        return self.base.iridescent();
+    }
+}
+
+#[test]
+fn test_layered() {
+    use crate::material::{Diffuse, Glass};
+    use crate::point::Point;
+    use crate::texture::SolidColour;
+
+    let normal = Vector::new(0.0, 1.0, 0.0);
+    let direction = Vector::new(0.4, -1.0, 0.1);
+    let ray = Ray::new(Point::new(0.0, 0.0, 0.0) - direction, direction);
+    let red = Diffuse::new(Colour::new(0.8, 0.1, 0.1));
+    let blue = Diffuse::new(Colour::new(0.1, 0.1, 0.8));
+    let record = HitRecord::new_from_ray(Point::new(0.0, 0.0, 0.0), normal, 1.0, &ray, red.clone());
+
+    // Mixing two diffuse materials a quarter of the way gives a diffuse material in between
+    let mix = Mix::new(red.clone(), blue, SolidColour::scalar(0.25));
+    let up = Vector::new(0.0, 1.0, 0.0);
+    let f = mix.eval(&ray, &record, up);
+    assert!((f.r - 0.625 / std::f64::consts::PI).abs() < 1e-12);
+    for _ in 0..50 {
+        let (scattered, weight) = mix.scatter(&ray, &record).unwrap();
+        let pdf = mix.pdf(&ray, &record, scattered.direction);
+        let f = mix.eval(&ray, &record, scattered.direction);
+        assert!((f.b / pdf - weight.b).abs() < 1e-9);
+    }
+
+    // Mixing in a specular material leaves nothing to light sample
+    let glassy = Mix::new(red.clone(), Glass::new(1.5), SolidColour::scalar(0.5));
+    assert_eq!(glassy.pdf(&ray, &record, up), 0.0);
+
+    // A clear coat adds a highlight, and takes light away from the base elsewhere
+    let coated = Coated::new(red.clone(), 1.5, 0.1, Colour::new(1.0, 1.0, 1.0));
+    let mirror = Vector::new(0.4, 1.0, 0.1);
+    assert!(coated.eval(&ray, &record, mirror).g > 10.0 * red.eval(&ray, &record, mirror).g);
+    assert!(coated.eval(&ray, &record, up).r < red.eval(&ray, &record, up).r);
+    let (mut total, n) = (0.0, 2000);
+    for _ in 0..n {
+        if let Some((_, weight)) = coated.scatter(&ray, &record) {
+            total += weight.r / n as f64;
+        }
+    }
+    assert!(total < 0.95 && total > 0.6);
+}
@@ -0,0 +1,124 @@
+use crate::colour::Colour;
+use crate::point::Point;
+use crate::utils::deg_to_rad;
+use crate::vector::Vector;
+
+// Lights with no area, which scattered rays can never hit, so are only reached by shadow rays
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Light {
+    // Intensity falls off with the square of the distance
+    Point {
+        position: Point,
+        intensity: Colour,
+    },
+    // A point light restricted to a cone, fading out between the inner and outer angles
+    Spot {
+        position: Point,
+        direction: Vector,
+        intensity: Colour,
+        inner_angle: f64,
+        outer_angle: f64,
+    },
+    // Parallel light travelling along the direction, such as from a distant sun
+    Directional {
+        direction: Vector,
+        irradiance: Colour,
+    },
+}
+
+impl Light {
+    // Returns the unit direction from the point towards the light, the light's irradiance
+    // there, on a surface facing it, and the distance to the light
+    pub fn sample(&self, p: Point) -> Option<(Vector, Colour, f64)> {
+        return match *self {
+            Light::Point {
+                position,
+                intensity,
+            } => {
+                let to_light = position - p;
+                let distance = to_light.length();
+                // A point at the light has no direction to it
+                if distance <= 0.0 {
+                    return None;
+                }
+                Some((
+                    to_light / distance,
+                    intensity * (1.0 / (distance * distance)),
+                    distance,
+                ))
+            }
+            Light::Spot {
+                position,
+                direction,
+                intensity,
+                inner_angle,
+                outer_angle,
+            } => {
+                let to_light = position - p;
+                let distance = to_light.length();
+                if distance <= 0.0 {
+                    return None;
+                }
+                let wi = to_light / distance;
+                let cos_theta = (-wi).dot(direction.unit());
+                let falloff = smoothstep(
+                    deg_to_rad(outer_angle).cos(),
+                    deg_to_rad(inner_angle).cos(),
+                    cos_theta,
+                );
+                if falloff == 0.0 {
+                    return None;
+                }
+                Some((wi, intensity * (falloff / (distance * distance)), distance))
+            }
+            Light::Directional {
+                direction,
+                irradiance,
+            } => Some((-direction.unit(), irradiance, f64::INFINITY)),
+        };
+    }
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    if edge0 >= edge1 {
+        return if x >= edge1 { 1.0 } else { 0.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+#[test]
+fn test_lights() {
+    let origin = Point::new(0.0, 0.0, 0.0);
+    let point = Light::Point {
+        position: Point::new(0.0, 2.0, 0.0),
+        intensity: Colour::new(4.0, 4.0, 4.0),
+    };
+    let (wi, irradiance, distance) = point.sample(origin).unwrap();
+    assert_eq!((wi.y(), irradiance.r, distance), (1.0, 1.0, 2.0));
+
+    let spot = Light::Spot {
+        position: Point::new(0.0, 1.0, 0.0),
+        direction: Vector::new(0.0, -1.0, 0.0),
+        intensity: Colour::new(1.0, 1.0, 1.0),
+        inner_angle: 20.0,
+        outer_angle: 30.0,
+    };
+    assert_eq!(spot.sample(origin).unwrap().1.r, 1.0);
+    // Half way across the penumbra, and then outside the cone
+    let edge = Point::new(deg_to_rad(25.0).tan(), 0.0, 0.0);
+    let (_, irradiance, distance) = spot.sample(edge).unwrap();
+    assert!(irradiance.r * distance * distance < 0.6 && irradiance.r * distance * distance > 0.4);
+    assert!(spot.sample(Point::new(1.0, 0.0, 0.0)).is_none());
+    // Nothing is sampled from the lights' own positions
+    assert!(point.sample(Point::new(0.0, 2.0, 0.0)).is_none());
+    assert!(spot.sample(Point::new(0.0, 1.0, 0.0)).is_none());
+
+    let sun = Light::Directional {
+        direction: Vector::new(0.0, -2.0, 0.0),
+        irradiance: Colour::new(3.0, 3.0, 3.0),
+    };
+    let (wi, _, distance) = sun.sample(origin).unwrap();
+    assert_eq!((wi.y(), distance), (1.0, f64::INFINITY));
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+
+use crate::colour::Colour;
+
+// A look up table read from an Adobe/Resolve .cube file, as used for grading looks. 1D tables
+// map each channel separately, and 3D ones the colour as a whole, interpolating between
+// entries in both cases.
+pub struct Lut {
+    size: usize,
+    three_d: bool,
+    domain_min: Colour,
+    domain_max: Colour,
+    // Red varies fastest, then green, then blue
+    entries: Vec<Colour>,
+}
+
+impl Lut {
+    pub fn load(path: &str) -> io::Result<Lut> {
+        return Lut::parse(&fs::read_to_string(path)?);
+    }
+
+    pub fn parse(text: &str) -> io::Result<Lut> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let triple = |fields: &[&str]| -> io::Result<Colour> {
+            let values = fields
+                .iter()
+                .map(|v| {
+                    v.parse::<f64>()
+                        .map_err(|_| invalid("Invalid .cube value."))
+                })
+                .collect::<io::Result<Vec<f64>>>()?;
+            if values.len() != 3 {
+                return Err(invalid("Expected three values per .cube line."));
+            }
+            return Ok(Colour::new(values[0], values[1], values[2]));
+        };
+
+        let mut size = 0;
+        let mut three_d = true;
+        let mut domain_min = Colour::new(0.0, 0.0, 0.0);
+        let mut domain_max = Colour::new(1.0, 1.0, 1.0);
+        let mut entries = Vec::new();
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.first() {
+                None => {}
+                Some(field) if field.starts_with('#') => {}
+                Some(&"TITLE") => {}
+                Some(&"LUT_1D_SIZE") | Some(&"LUT_3D_SIZE") => {
+                    three_d = fields[0] == "LUT_3D_SIZE";
+                    size = fields
+                        .get(1)
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .ok_or_else(|| invalid("Invalid .cube size."))?;
+                }
+                Some(&"DOMAIN_MIN") => domain_min = triple(&fields[1..])?,
+                Some(&"DOMAIN_MAX") => domain_max = triple(&fields[1..])?,
+                // Resolve's form of the domain, the same for every channel
+                Some(&"LUT_1D_INPUT_RANGE") | Some(&"LUT_3D_INPUT_RANGE") => {
+                    let range = fields[1..]
+                        .iter()
+                        .map(|v| v.parse::<f64>().ok())
+                        .collect::<Option<Vec<f64>>>()
+                        .filter(|range| range.len() == 2)
+                        .ok_or_else(|| invalid("Invalid .cube input range."))?;
+                    domain_min = Colour::new(range[0], range[0], range[0]);
+                    domain_max = Colour::new(range[1], range[1], range[1]);
+                }
+                // Other tools' metadata
+                Some(field) if field.parse::<f64>().is_err() => {}
+                Some(_) => entries.push(triple(&fields)?),
+            }
+        }
+        let expected = if three_d {
+            size.checked_pow(3)
+                .ok_or_else(|| invalid("Invalid .cube size."))?
+        } else {
+            size
+        };
+        if size < 2 || entries.len() != expected {
+            return Err(invalid("Wrong number of .cube entries."));
+        }
+        return Ok(Lut {
+            size,
+            three_d,
+            domain_min,
+            domain_max,
+            entries,
+        });
+    }
+
+    pub fn apply(&self, colour: Colour) -> Colour {
+        let n = (self.size - 1) as f64;
+        // Position along each axis in table entries, clamped to the domain
+        let position = |value: f64, min: f64, max: f64| -> (usize, f64) {
+            let x = ((value - min) / (max - min)).clamp(0.0, 1.0) * n;
+            let i = (x as usize).min(self.size - 2);
+            return (i, x - i as f64);
+        };
+        let (ri, rt) = position(colour.r, self.domain_min.r, self.domain_max.r);
+        let (gi, gt) = position(colour.g, self.domain_min.g, self.domain_max.g);
+        let (bi, bt) = position(colour.b, self.domain_min.b, self.domain_max.b);
+
+        if !self.three_d {
+            let lerp = |i: usize, t: f64| -> Colour {
+                return self.entries[i].interpolate(self.entries[i + 1], t);
+            };
+            return Colour::new(lerp(ri, rt).r, lerp(gi, gt).g, lerp(bi, bt).b);
+        }
+        let entry = |r: usize, g: usize, b: usize| -> Colour {
+            return self.entries[(b * self.size + g) * self.size + r];
+        };
+        let mut out = Colour::new(0.0, 0.0, 0.0);
+        for (db, wb) in [(0, 1.0 - bt), (1, bt)] {
+            for (dg, wg) in [(0, 1.0 - gt), (1, gt)] {
+                for (dr, wr) in [(0, 1.0 - rt), (1, rt)] {
+                    out += entry(ri + dr, gi + dg, bi + db) * (wr * wg * wb);
+                }
+            }
+        }
+        return out;
+    }
+}
+
+#[test]
+fn test_lut() {
+    // An identity cube, written out the way grading tools do
+    let mut text = String::from("# Identity\nTITLE \"identity\"\nLUT_3D_SIZE 3\n\n");
+    for b in 0..3 {
+        for g in 0..3 {
+            for r in 0..3 {
+                text.push_str(&format!(
+                    "{} {} {}\n",
+                    r as f64 / 2.0,
+                    g as f64 / 2.0,
+                    b as f64 / 2.0
+                ));
+            }
+        }
+    }
+    let identity = Lut::parse(&text).unwrap();
+    let c = identity.apply(Colour::new(0.3, 0.65, 0.9));
+    assert!((c.r - 0.3).abs() < 1e-12 && (c.g - 0.65).abs() < 1e-12 && (c.b - 0.9).abs() < 1e-12);
+    // Values outside the domain are clamped to it
+    assert_eq!(identity.apply(Colour::new(2.0, -1.0, 0.5)).r, 1.0);
+
+    // A 1D table inverting each channel
+    let invert = Lut::parse("LUT_1D_SIZE 2\n1 1 1\n0 0 0\n").unwrap();
+    let c = invert.apply(Colour::new(0.25, 0.5, 1.0));
+    assert!((c.r - 0.75).abs() < 1e-12 && (c.g - 0.5).abs() < 1e-12 && c.b.abs() < 1e-12);
+
+    // Resolve's input ranges and unknown keywords are read around
+    let halved =
+        Lut::parse("LUT_1D_INPUT_RANGE 0 2\nLUT_IN_VIDEO_RANGE\nLUT_1D_SIZE 2\n0 0 0\n1 1 1\n");
+    let c = halved.unwrap().apply(Colour::new(1.0, 0.5, 2.0));
+    assert!((c.r - 0.5).abs() < 1e-12 && (c.g - 0.25).abs() < 1e-12 && (c.b - 1.0).abs() < 1e-12);
+
+    assert!(Lut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+    assert!(Lut::parse("LUT_3D_SIZE 18446744073709551615\n0 0 0\n").is_err());
+}
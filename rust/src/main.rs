@@ -1,26 +1,84 @@
+#![allow(clippy::needless_return)]
+
+mod animation;
+mod aperture;
+mod background;
 mod camera;
 mod colour;
+mod colour_space;
+mod csg;
+mod film;
+mod filter;
 mod hittable;
+mod image;
+mod integrator;
+mod layered;
+mod light;
+mod lut;
+mod mask;
 mod material;
+mod medium;
+mod microfacet;
+mod normal_map;
 mod point;
+mod principled;
 mod ray;
+mod sampling;
+mod scene;
+mod sky;
+mod spectrum;
 mod sphere;
+mod stats;
+mod stereo;
+mod texture;
+mod thin_film;
+mod tile;
+mod transform;
 mod utils;
 mod vector;
+mod volume;
 
 use indicatif::{ProgressBar, ProgressStyle};
-use rand::Rng;
-use rayon::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::sync::Arc;
+use std::time::Instant;
 
-use camera::Camera;
+use animation::{CameraTracks, FrameRange, Interpolation, Keyframe};
+use aperture::{Aperture, ApertureShape};
+use background::{Background, EnvironmentMap, Gradient};
+use camera::{
+    Bokeh, Camera, Equirectangular, Fisheye, Focus, Lens, Orthographic, Perspective, Projection,
+};
 use colour::Colour;
+use colour_space::{ColourSpace, Encoding, OutputTransform};
+use csg::{Csg, Operation};
+use film::Film;
+use filter::Filter;
 use hittable::{Environment, Hit};
-use material::{Diffuse, Glass, Material, Metal};
+use image::Image;
+use integrator::{ray_colour, ray_colour_spectral};
+use layered::{Coated, Mix};
+use light::Light;
+use lut::Lut;
+use mask::{AlphaMode, Masked};
+use material::{Conductor, Diffuse, Glass, Material, Metal, MetalPreset, RoughGlass, Subsurface};
+use medium::Medium;
+use normal_map::{NormalMapped, SurfaceMap};
 use point::Point;
-use ray::Ray;
+use principled::Principled;
+use scene::Scene;
+use sky::{Sky, SunPosition};
+use spectrum::{Dispersion, D_LINE};
 use sphere::Sphere;
+use stats::RenderReport;
+use stereo::{pack, EyeView, Stereo, StereoLayout};
+use texture::{Checker, ImageTexture, SolidColour};
+use thin_film::ThinFilm;
+use tile::{generate_tiles, render_tiles, Tile, TileOrder};
+use transform::{TransformTrack, Transformed};
 use utils::write_file;
+use volume::{Density, Grid, Noise, Volume};
 
 use crate::vector::Vector;
 
@@ -31,7 +89,7 @@ const IMAGES_DIR: &str = "images";
 const OUTPUT_IMAGE: &str = "final";
 const ANTIALIAS_SAMPLES: i64 = 100;
 const MAX_DEPTH: i32 = 50;
-const V_FOV: i32 = 20;
+const V_FOV: f64 = 20.0;
 const LOOK_FROM: Point = Point {
     v: Vector {
         xyz: [13.0, 2.0, 3.0],
@@ -46,40 +104,160 @@ const V_UP: Vector = Vector {
     xyz: [0.0, 1.0, 0.0],
 };
 const APERTURE: f64 = 0.01;
+// Overrides V_FOV and APERTURE, e.g. Some(Lens { focal_length: 50.0, sensor_height: 24.0, f_number: 2.8 })
+const LENS: Option<Lens> = None;
+const APERTURE_SHAPE: ApertureShape = ApertureShape::Circle;
+const CAT_EYE: f64 = 0.0;
+const CHROMATIC_ABERRATION: f64 = 0.0;
+const FOCUS: Focus = Focus::LookAt;
+// e.g. Projection::Fisheye { fov: 180.0, mapping: FisheyeMapping::Equisolid }
+const PROJECTION: Projection = Projection::Perspective;
+// e.g. Stereo::Rig { interocular: 0.065, convergence: Convergence::OffAxis, convergence_distance: 10.0 }
+const STEREO: Stereo = Stereo::Mono;
+const STEREO_LAYOUT: StereoLayout = StereoLayout::SideBySide;
+const TILE_SIZE: u32 = 32;
+const TILE_ORDER: TileOrder = TileOrder::Hilbert;
+const THREADS: usize = 0; // 0 uses every logical core
+const FILTER: Filter = Filter::Box { radius: 0.5 };
+// e.g. Some("images/stats.json")
+const STATS_JSON: Option<&str> = None;
+// Renders a numbered image sequence, e.g. Some(FrameRange { start: 1, end: 48, step: 1 })
+const FRAMES: Option<FrameRange> = None;
+const FPS: f64 = 24.0;
+const SCENE_SEED: u64 = 42;
+// Space the scene's colours are given in and light is computed in, e.g. ColourSpace::AcesCg
+const WORKING_SPACE: ColourSpace = ColourSpace::LinearSrgb;
+const OUTPUT_SPACE: ColourSpace = ColourSpace::LinearSrgb;
+// e.g. Encoding::Srgb for a standard display
+const OUTPUT_ENCODING: Encoding = Encoding::Gamma(2.0);
+// .cube look applied to the encoded output, e.g. Some("looks/film.cube")
+const LOOK: Option<&str> = None;
+// Equirectangular .hdr, .exr or .ppm image lighting the scene in place of the sky gradient
+const ENVIRONMENT_MAP: Option<&str> = None;
+const ENVIRONMENT_ROTATION: f64 = 0.0; // Degrees about the vertical axis
+const ENVIRONMENT_INTENSITY: f64 = 1.0;
+// Primaries of the environment map's linear pixels
+const ENVIRONMENT_SPACE: ColourSpace = ColourSpace::LinearSrgb;
+// Daylight from a physical sky in place of the gradient, unless an environment map is set,
+// e.g. Some(SunPosition::Angles { elevation: 30.0, azimuth: 120.0 })
+const SUN: Option<SunPosition> = None;
+const SKY_TURBIDITY: f64 = 3.0;
+// Scales the sky's kcd/m^2
+const SKY_INTENSITY: f64 = 0.05;
+// e.g. &[Light::Point { position: Point { v: Vector { xyz: [0.0, 4.0, 2.0] } }, intensity: Colour { r: 20.0, g: 20.0, b: 20.0 } }]
+const LIGHTS: &[Light] = &[];
+// Traces each sample at a few wavelengths, for colour that mixes like real light
+const SPECTRAL: bool = false;
+// Swaps the scene's fuzzed metals for GGX conductors made from measured metals
+const MICROFACET_METALS: bool = false;
+// Frosts the centre sphere when above zero
+const GLASS_ROUGHNESS: f64 = 0.0;
+// Colour left after light crosses the centre sphere, e.g. Some(Colour { r: 0.2, g: 0.6, b: 0.4 })
+const GLASS_TINT: Option<Colour> = None;
+// Splits white light through the centre sphere when smooth, e.g. Some(Dispersion::DENSE_FLINT)
+const GLASS_DISPERSION: Option<Dispersion> = None;
+// Iridescent coating on the centre sphere when smooth, e.g. a soap film of
+// Some(ThinFilm { thickness: 400.0, refractive_idx: 1.33 })
+const GLASS_FILM: Option<ThinFilm> = None;
+// Makes the centre sphere translucent, like wax or marble, in place of glass, given the colour
+// it takes on where it's thick, e.g. Some(Colour { r: 0.9, g: 0.75, b: 0.6 })
+const SUBSURFACE_ALBEDO: Option<Colour> = None;
+// How far light travels under the surface between scattering events, in each channel
+const SUBSURFACE_MEAN_FREE_PATH: Colour = Colour {
+    r: 0.3,
+    g: 0.15,
+    b: 0.08,
+};
+// Coating on the right sphere's GGX metal
+const METAL_FILM: Option<ThinFilm> = None;
+// Oren-Nayar facet roughness of the scene's plain diffuse surfaces, in degrees
+const DIFFUSE_SIGMA: f64 = 0.0;
+// Builds the scene's diffuse surfaces from the principled material, or from plain diffuse
+// ones when false
+const PRINCIPLED_MATERIALS: bool = true;
+// Checks the left sphere between lacquered blue and gold, instead of its usual material
+const LAYERED_MATERIALS: bool = false;
+// Colour image wrapped around the left sphere, e.g. Some("textures/earth.ppm"), and how its
+// pixels are stored
+const LEFT_TEXTURE: Option<&str> = None;
+const LEFT_TEXTURE_ENCODING: Encoding = Encoding::Srgb;
+const LEFT_TEXTURE_SPACE: ColourSpace = ColourSpace::LinearSrgb;
+// Tangent space normal map image for the left sphere
+const NORMAL_MAP: Option<&str> = None;
+// Height map image for the left sphere, used when there's no normal map
+const BUMP_MAP: Option<&str> = None;
+const BUMP_STRENGTH: f64 = 0.02;
+// Hangs a cloud of fractal noise over the scene, or of a grid of densities if one's given
+const CLOUD: bool = false;
+// Raw little endian f32 densities and their resolution, e.g. Some(("smoke.raw", [64, 64, 64]))
+const CLOUD_GRID: Option<(&str, [usize; 3])> = None;
+// Makes the cloud a fireball, e.g. Colour { r: 6.0, g: 2.0, b: 0.4 }
+const CLOUD_EMISSION: Colour = Colour {
+    r: 0.0,
+    g: 0.0,
+    b: 0.0,
+};
+// Cuts checkered holes through the left sphere, e.g. Some(AlphaMode::Threshold(0.5))
+const ALPHA_MASK: Option<AlphaMode> = None;
+// Builds the centre object from two spheres, e.g. Some(Operation::Intersection) for a lens or
+// Some(Operation::Difference) for a hollow shell
+const CENTRE_CSG: Option<Operation> = None;
 
-fn ray_colour(ray: &Ray, world: &Environment, depth: i32) -> Colour {
-    if depth <= 0 {
-        return Colour::new(0.0, 0.0, 0.0);
-    }
+fn camera_tracks() -> CameraTracks {
+    // e.g. look_from: Some(Track::new(vec![Keyframe::new(0.0, LOOK_FROM), ...], Interpolation::CatmullRom))
+    return CameraTracks {
+        look_from: None,
+        look_at: None,
+        v_fov: None,
+        focus_distance: None,
+    };
+}
 
-    if let Some(rec) = world.hit(ray, 0.001, f64::INFINITY) {
-        if let Some((scattered, colour)) = rec.material.scatter(ray, &rec) {
-            return colour * ray_colour(&scattered, world, depth - 1);
-        } else {
-            return Colour::new(0.0, 0.0, 0.0);
+fn create_background() -> Arc<dyn Background> {
+    return match ENVIRONMENT_MAP {
+        Some(path) => {
+            let image = Image::load(path).expect("Failed to load environment map.");
+            Arc::new(EnvironmentMap::new(
+                image.into_working(Encoding::Linear, ENVIRONMENT_SPACE, WORKING_SPACE),
+                ENVIRONMENT_ROTATION,
+                ENVIRONMENT_INTENSITY,
+            ))
         }
-    } else {
-        let t = 0.5 * (ray.direction.unit().y() + 1.0);
-        let start = Colour::new(1.0, 1.0, 1.0);
-        let end = Colour::new(0.5, 0.7, 1.0);
-        return start.interpolate(end, t);
+        None => match SUN {
+            Some(sun) => Arc::new(Sky::new(sun, SKY_TURBIDITY, SKY_INTENSITY, WORKING_SPACE)),
+            None => Arc::new(Gradient {
+                start: Colour::new(1.0, 1.0, 1.0),
+                end: Colour::new(0.5, 0.7, 1.0),
+            }),
+        },
     };
 }
 
-fn create_scene(n: i32) -> Environment {
+fn create_scene(n: i32, time: f64) -> Environment {
     let mut world = Environment {
         hittables: Vec::new(),
     };
 
+    let diffuse = |colour: Colour| -> Arc<dyn Material> {
+        if PRINCIPLED_MATERIALS {
+            return Arc::new(Principled::new(SolidColour::new(colour)));
+        }
+        if DIFFUSE_SIGMA > 0.0 {
+            return Diffuse::rough(colour, DIFFUSE_SIGMA);
+        }
+        return Diffuse::new(colour);
+    };
+
     // Ground
-    let ground_mat = Diffuse::new(Colour::new(0.5, 0.5, 0.5));
+    let ground_mat = diffuse(Colour::new(0.5, 0.5, 0.5));
     world.add(Sphere::new(
         Point::new(0.0, -1000.0, 0.0),
         1000.0,
         ground_mat,
     ));
 
-    let mut rng = rand::thread_rng();
+    // Seeded so that every frame of an animation sees the same scene
+    let mut rng = StdRng::seed_from_u64(SCENE_SEED);
     for a in -n..n {
         for b in -n..n {
             let choose_mat: f64 = rng.gen();
@@ -91,9 +269,22 @@ fn create_scene(n: i32) -> Environment {
 
             let material: Arc<dyn Material>;
             if choose_mat < 0.8 {
-                material = Diffuse::new(Colour::random());
+                material = diffuse(Colour::random(&mut rng));
             } else if choose_mat < 0.95 {
-                material = Metal::new(Colour::random_range(0.0..0.5), rng.gen_range(0.0..0.5));
+                let colour = Colour::random_range(&mut rng, 0.0..0.5);
+                let fuzz = rng.gen_range(0.0..0.5);
+                material = if MICROFACET_METALS {
+                    let presets = [
+                        MetalPreset::Gold,
+                        MetalPreset::Copper,
+                        MetalPreset::Aluminium,
+                        MetalPreset::Silver,
+                    ];
+                    let preset = presets[rng.gen_range(0..presets.len())];
+                    Conductor::preset(preset, fuzz, fuzz)
+                } else {
+                    Metal::new(colour, fuzz)
+                };
             } else {
                 material = Glass::new(1.5);
             }
@@ -101,59 +292,329 @@ fn create_scene(n: i32) -> Environment {
         }
     }
 
-    let left_mat = Diffuse::new(Colour::new(0.1, 0.2, 0.5));
-    let centre_mat = Glass::new(1.5);
-    let right_mat = Metal::new(Colour::new(0.7, 0.6, 0.5), 0.0);
+    let left_mat = if let Some(path) = LEFT_TEXTURE {
+        let texture = ImageTexture::load_colour(
+            path,
+            LEFT_TEXTURE_ENCODING,
+            LEFT_TEXTURE_SPACE,
+            WORKING_SPACE,
+        )
+        .expect("Failed to load texture.");
+        Arc::new(Principled::new(texture))
+    } else if LAYERED_MATERIALS {
+        let lacquer = Coated::new(
+            diffuse(Colour::new(0.1, 0.2, 0.5)),
+            1.5,
+            0.05,
+            Colour::new(0.9, 0.9, 0.8),
+        );
+        let gold = Conductor::preset(MetalPreset::Gold, 0.2, 0.2);
+        let pattern = Checker::new(0.25, SolidColour::scalar(0.0), SolidColour::scalar(1.0));
+        Mix::new(lacquer, gold, pattern)
+    } else if PRINCIPLED_MATERIALS {
+        // Checked, lacquered plastic
+        Arc::new(Principled {
+            roughness: SolidColour::scalar(0.8),
+            clearcoat: SolidColour::scalar(1.0),
+            ..Principled::new(Checker::new(
+                0.25,
+                SolidColour::new(Colour::new(0.1, 0.2, 0.5)),
+                SolidColour::new(Colour::new(0.9, 0.9, 0.9)),
+            ))
+        })
+    } else {
+        diffuse(Colour::new(0.1, 0.2, 0.5))
+    };
+    let centre_mat: Arc<dyn Material> = if let Some(albedo) = SUBSURFACE_ALBEDO {
+        Subsurface::new(albedo, SUBSURFACE_MEAN_FREE_PATH, 1.4, 0.2)
+    } else {
+        match (GLASS_TINT, GLASS_ROUGHNESS > 0.0) {
+            (Some(tint), true) => {
+                RoughGlass::with_medium(1.5, GLASS_ROUGHNESS, Medium::from_colour(tint, 2.0))
+            }
+            (None, true) => RoughGlass::new(1.5, GLASS_ROUGHNESS),
+            (tint, false) => Arc::new(Glass {
+                refractive_idx: GLASS_DISPERSION.map_or(1.5, |dispersion| dispersion.ior(D_LINE)),
+                interior: tint.map(|tint| Medium::from_colour(tint, 2.0)),
+                dispersion: GLASS_DISPERSION,
+                film: GLASS_FILM,
+            }),
+        }
+    };
+    let right_mat: Arc<dyn Material> = if MICROFACET_METALS {
+        // Brushed along one tangent direction
+        match METAL_FILM {
+            Some(film) => Conductor::with_film(MetalPreset::Aluminium, 0.1, 0.4, film),
+            None => Conductor::preset(MetalPreset::Aluminium, 0.1, 0.4),
+        }
+    } else {
+        Metal::new(Colour::new(0.7, 0.6, 0.5), 0.0)
+    };
 
-    world.add(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, left_mat));
-    world.add(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, centre_mat));
+    let mut left: Box<dyn Hit> = Box::new(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, left_mat));
+    if let Some(mode) = ALPHA_MASK {
+        let holes = Checker::new(0.25, SolidColour::scalar(1.0), SolidColour::scalar(0.0));
+        left = Box::new(Masked::new(left, holes, mode));
+    }
+    match (NORMAL_MAP, BUMP_MAP) {
+        (Some(path), _) => {
+            let map = ImageTexture::load(path).expect("Failed to load normal map.");
+            world.add(NormalMapped::new(left, SurfaceMap::Normal(map)));
+        }
+        (None, Some(path)) => {
+            let height = ImageTexture::load(path).expect("Failed to load bump map.");
+            let map = SurfaceMap::Bump {
+                height,
+                strength: BUMP_STRENGTH,
+            };
+            world.add(NormalMapped::new(left, map));
+        }
+        (None, None) => world.add(left),
+    }
+    let bounce = TransformTrack::translation(
+        vec![
+            Keyframe::new(0.0, Vector::new(0.0, 0.0, 0.0)),
+            Keyframe::new(1.0, Vector::new(0.0, 0.5, 0.0)),
+            Keyframe::new(2.0, Vector::new(0.0, 0.0, 0.0)),
+        ],
+        Interpolation::CatmullRom,
+    );
+    let centre = Point::new(0.0, 1.0, 0.0);
+    let ball = |offset: f64, radius: f64| -> Sphere {
+        return Sphere::new(
+            centre + Vector::new(offset, 0.0, 0.0),
+            radius,
+            centre_mat.clone(),
+        );
+    };
+    let centre_object: Box<dyn Hit> = match CENTRE_CSG {
+        None => Box::new(ball(0.0, 1.0)),
+        // Bulges towards the camera from either side
+        Some(Operation::Intersection) => Box::new(Csg::new(
+            Operation::Intersection,
+            ball(-1.6, 2.0),
+            ball(1.6, 2.0),
+        )),
+        Some(Operation::Difference) => Box::new(Csg::new(
+            Operation::Difference,
+            ball(0.0, 1.0),
+            ball(0.0, 0.85),
+        )),
+        Some(Operation::Union) => {
+            Box::new(Csg::new(Operation::Union, ball(0.0, 0.8), ball(0.6, 0.6)))
+        }
+    };
+    world.add(Transformed::new(centre_object, bounce.evaluate(time)));
     world.add(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, right_mat));
+
+    if CLOUD {
+        let (centre, radius) = (Point::new(2.0, 1.6, 1.6), 1.0);
+        let corner = Vector::new(radius, radius, radius);
+        let (min, max) = (centre - corner, centre + corner);
+        let density: Arc<dyn Density> = match CLOUD_GRID {
+            Some((path, size)) => Grid::load(path, size, min, max).expect("Failed to load grid."),
+            None => Noise::new(centre, radius, 1.5, 5, SCENE_SEED as u32),
+        };
+        // Mostly scattering, like water droplets, with a little absorption, like soot
+        let medium = Medium {
+            anisotropy: 0.3,
+            emission: CLOUD_EMISSION,
+            ..Medium::heterogeneous(
+                density,
+                Colour::new(0.3, 0.3, 0.3),
+                Colour::new(8.0, 8.0, 8.0),
+            )
+        };
+        world.add(Volume::new(min, max, medium));
+    }
     return world;
 }
 
+fn create_camera(view: &EyeView, v_fov: f64, focus_distance: f64) -> Box<dyn Camera> {
+    let (look_from, look_at) = (view.look_from, view.look_at);
+    return match PROJECTION {
+        Projection::Perspective => {
+            let camera = match LENS {
+                Some(lens) => Perspective::from_lens(
+                    look_from,
+                    look_at,
+                    V_UP,
+                    ASPECT_RATIO,
+                    lens,
+                    focus_distance,
+                ),
+                None => Perspective::new(
+                    look_from,
+                    look_at,
+                    V_UP,
+                    ASPECT_RATIO,
+                    v_fov,
+                    APERTURE,
+                    focus_distance,
+                ),
+            };
+            Box::new(
+                camera
+                    .with_bokeh(Bokeh {
+                        aperture: Aperture::new(APERTURE_SHAPE)
+                            .expect("Failed to load aperture mask."),
+                        cat_eye: CAT_EYE,
+                        chromatic_aberration: CHROMATIC_ABERRATION,
+                    })
+                    .with_shift(view.shift),
+            )
+        }
+        Projection::Orthographic { height } => Box::new(Orthographic::new(
+            look_from,
+            look_at,
+            V_UP,
+            ASPECT_RATIO,
+            height,
+        )),
+        Projection::Fisheye { fov, mapping } => Box::new(Fisheye::new(
+            look_from,
+            look_at,
+            V_UP,
+            ASPECT_RATIO,
+            fov,
+            mapping,
+        )),
+        Projection::Equirectangular => Box::new(
+            Equirectangular::new(look_from, look_at, V_UP).with_eye_offset(view.ods_offset),
+        ),
+    };
+}
+
+fn render(
+    cam: &dyn Camera,
+    scene: &Scene,
+    image_height: u32,
+    tiles: &[Tile],
+    bar: &ProgressBar,
+) -> Vec<Colour> {
+    let mut film = Film::new(IMAGE_WIDTH, image_height, FILTER);
+    render_tiles(tiles, THREADS, &mut film, bar, |tile, film_tile| {
+        let mut rng = rand::thread_rng();
+        for (i, y) in tile.pixels() {
+            for _ in 0..ANTIALIAS_SAMPLES {
+                // Film coordinates run down from the top of the image, the camera's up from the bottom
+                let px = (i as f64) + rng.gen::<f64>();
+                let py = (y as f64) + rng.gen::<f64>();
+                let u = px / ((IMAGE_WIDTH - 1) as f64);
+                let v = ((image_height as f64) - py) / ((image_height - 1) as f64);
+                let colour = match cam.sample_ray(u, v) {
+                    Some((ray, weight)) => {
+                        stats::camera_ray();
+                        let radiance = if SPECTRAL {
+                            ray_colour_spectral(&ray, scene, MAX_DEPTH)
+                        } else {
+                            ray_colour(&ray, scene, MAX_DEPTH)
+                        };
+                        weight * radiance
+                    }
+                    None => Colour::new(0.0, 0.0, 0.0),
+                };
+                film_tile.add_sample(px, py, colour);
+            }
+        }
+        stats::flush();
+    });
+    return film.resolve();
+}
+
 fn main() {
     // Image
     let image_height = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as u32;
-
-    // Camera
-    let cam = Camera::new(LOOK_FROM, LOOK_AT, V_UP, ASPECT_RATIO, V_FOV, APERTURE);
-
-    // World
-    let world = create_scene(8);
+    let tracks = camera_tracks();
+    let background = create_background();
+    let output = OutputTransform {
+        space: OUTPUT_SPACE,
+        encoding: OUTPUT_ENCODING,
+        look: LOOK.map(|path| Lut::load(path).expect("Failed to load look.")),
+    };
 
     // File
     println!("\n⏳ Rendering...\n");
-    let fpath = format!("{}/{}.ppm", IMAGES_DIR, OUTPUT_IMAGE);
-    let mut out = format!("P3\n{} {}\n255\n", IMAGE_WIDTH, image_height);
-    let bar = ProgressBar::new(image_height as u64);
+    let start = Instant::now();
+    let frames: Vec<Option<u32>> = match FRAMES {
+        Some(range) => range.frames().map(Some).collect(),
+        None => vec![None],
+    };
+    let views_per_frame = match STEREO {
+        Stereo::Mono => 1,
+        _ => 2,
+    };
+    let tiles = generate_tiles(IMAGE_WIDTH, image_height, TILE_SIZE, TILE_ORDER);
+    let bar = ProgressBar::new((tiles.len() * views_per_frame * frames.len()) as u64);
     bar.set_style(ProgressStyle::with_template("[{elapsed_precise}] {bar:100.cyan/blue} {percent}/100%")
         .unwrap()
         .progress_chars("█░"));
 
-    for j in (0..image_height).rev() {
-        let pixels: Vec<Colour> = (0..IMAGE_WIDTH)
-            .into_par_iter()
-            .map(|i| {
-                let mut rng = rand::thread_rng();
-                let mut pixel = Colour::new(0.0, 0.0, 0.0);
-                for _ in 0..ANTIALIAS_SAMPLES {
-                    let u_r: f64 = rng.gen();
-                    let v_r: f64 = rng.gen();
-                    let u = ((i as f64) + u_r) / ((IMAGE_WIDTH - 1) as f64);
-                    let v = ((j as f64) + v_r) / ((image_height - 1) as f64);
-                    let ray = cam.get_ray(u, v);
-                    pixel += ray_colour(&ray, &world, MAX_DEPTH);
-                }
-                return pixel;
+    for frame in frames.into_iter() {
+        let time = frame.map_or(0.0, |f| f as f64 / FPS);
+
+        // World
+        let world = create_scene(8, time);
+
+        // Camera
+        let look_from = tracks
+            .look_from
+            .as_ref()
+            .map_or(LOOK_FROM, |t| t.evaluate(time));
+        let look_at = tracks
+            .look_at
+            .as_ref()
+            .map_or(LOOK_AT, |t| t.evaluate(time));
+        let v_fov = tracks.v_fov.as_ref().map_or(V_FOV, |t| t.evaluate(time));
+        let focus_distance = match &tracks.focus_distance {
+            Some(track) => track.evaluate(time),
+            None => FOCUS.distance(look_from, look_at, &world),
+        };
+        let scene = Scene::new(world, background.clone(), LIGHTS.to_vec(), WORKING_SPACE);
+        let views = STEREO.views(look_from, look_at, V_UP);
+
+        let mut images: Vec<Vec<Colour>> = views
+            .iter()
+            .map(|view| {
+                let cam = create_camera(view, v_fov, focus_distance);
+                render(cam.as_ref(), &scene, image_height, &tiles, &bar)
             })
             .collect();
 
-        for pixel in pixels.into_iter() {
-            out.push_str(&format!("{}\n", pixel.render(ANTIALIAS_SAMPLES))[..]);
+        let outputs = if images.len() == 2 {
+            let right = images.pop().unwrap();
+            let left = images.pop().unwrap();
+            let packed = pack(STEREO_LAYOUT, IMAGE_WIDTH, image_height, left, right);
+            let names = match STEREO_LAYOUT {
+                StereoLayout::Separate => vec!["_left", "_right"],
+                _ => vec![""],
+            };
+            names.into_iter().zip(packed).collect()
+        } else {
+            vec![("", (IMAGE_WIDTH, image_height, images.pop().unwrap()))]
+        };
+        let frame_suffix = frame.map_or(String::new(), |f| format!("_{:04}", f));
+        for (suffix, (width, height, pixels)) in outputs.into_iter() {
+            let fpath = format!(
+                "{}/{}{}{}.ppm",
+                IMAGES_DIR, OUTPUT_IMAGE, frame_suffix, suffix
+            );
+            let pixels = pixels
+                .into_iter()
+                .map(|c| output.apply(c, WORKING_SPACE))
+                .collect();
+            let image = Image::new(width, height, pixels);
+            write_file(&fpath, &image.to_ppm()).expect("Failed when writing file.");
         }
-        bar.inc(1);
     }
-    write_file(&fpath, &out).expect("Failed when writing file.");
+    let report = RenderReport {
+        wall_time: start.elapsed(),
+        stats: stats::collect(),
+    };
     bar.finish();
     println!("\n\n✅ Rendering complete.\n");
+    println!("{}\n", report);
+    if let Some(path) = STATS_JSON {
+        write_file(&path.to_string(), &report.to_json()).expect("Failed when writing stats.");
+    }
 }
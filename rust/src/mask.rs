@@ -0,0 +1,126 @@
+use rand::Rng;
+use std::sync::Arc;
+
+use crate::hittable::{Hit, HitRecord, Interval};
+use crate::ray::Ray;
+use crate::texture::Texture;
+
+// Distance along the ray past a rejected hit to resume searching from, so it isn't found again
+const RESUME_OFFSET: f64 = 1e-6;
+
+// How opacity decides whether a ray passes through a surface
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum AlphaMode {
+    // Surfaces at least this opaque are solid, and the rest are cut away
+    Threshold(f64),
+    // Rays hit with a probability equal to the opacity, so partial opacity averages out over
+    // samples into soft, semi-transparent edges
+    Stochastic,
+}
+
+// Cuts holes in an object where an opacity texture's red channel is low, such as the gaps
+// between the leaves on a textured quad. Everything intersects through `hit`, so shadow rays
+// pass through the holes as well. In constructive solid geometry, only the spans with solid
+// surfaces at both ends are kept.
+pub struct Masked {
+    object: Box<dyn Hit>,
+    opacity: Arc<dyn Texture>,
+    mode: AlphaMode,
+}
+
+impl Masked {
+    pub fn new(object: impl Hit + 'static, opacity: Arc<dyn Texture>, mode: AlphaMode) -> Masked {
+        return Masked {
+            object: Box::new(object),
+            opacity,
+            mode,
+        };
+    }
+
+    fn opaque(&self, rec: &HitRecord) -> bool {
+        let alpha = self.opacity.value(rec.u, rec.v, rec.p).r;
+        return match self.mode {
+            AlphaMode::Threshold(cutoff) => alpha >= cutoff,
+            AlphaMode::Stochastic => rand::thread_rng().gen::<f64>() < alpha,
+        };
+    }
+}
+
+impl Hit for Masked {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // Carries on past rejected hits to find the object's next surface along the ray
+        let mut t_min = t_min;
+        loop {
+            let rec = self.object.hit(ray, t_min, t_max)?;
+            if self.opaque(&rec) {
+                return Some(rec);
+            }
+            t_min = rec.t + RESUME_OFFSET;
+        }
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        // A hole at either end leaves no surface to bound the span by, so it's dropped
+        return self
+            .object
+            .intervals(ray)
+            .into_iter()
+            .filter(|interval| self.opaque(&interval.enter) && self.opaque(&interval.exit))
+            .collect();
+    }
+}
+
+#[test]
+fn test_mask() {
+    use crate::colour::Colour;
+    use crate::material::Diffuse;
+    use crate::point::Point;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColour;
+    use crate::vector::Vector;
+
+    let sphere = || {
+        Sphere::new(
+            Point::new(0.0, 0.0, 0.0),
+            1.0,
+            Diffuse::new(Colour::new(0.5, 0.5, 0.5)),
+        )
+    };
+    let ray = Ray::new(Point::new(5.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0));
+
+    // Solid above the threshold, and missed entirely below it
+    let solid = Masked::new(
+        sphere(),
+        SolidColour::scalar(0.6),
+        AlphaMode::Threshold(0.5),
+    );
+    assert_eq!(solid.hit(&ray, 0.001, f64::INFINITY).unwrap().t, 4.0);
+    let clear = Masked::new(
+        sphere(),
+        SolidColour::scalar(0.4),
+        AlphaMode::Threshold(0.5),
+    );
+    assert!(clear.hit(&ray, 0.001, f64::INFINITY).is_none());
+
+    // Cutting away the near side reveals the far side, from the inside
+    struct FarSide;
+    impl Texture for FarSide {
+        fn value(&self, _u: f64, _v: f64, p: Point) -> Colour {
+            return Colour::new(if p.x() < 0.0 { 1.0 } else { 0.0 }, 0.0, 0.0);
+        }
+    }
+    let half = Masked::new(sphere(), Arc::new(FarSide), AlphaMode::Threshold(0.5));
+    let rec = half.hit(&ray, 0.001, f64::INFINITY).unwrap();
+    assert!(rec.t == 6.0 && !rec.front_face);
+
+    // Stochastic masks let through the transparent fraction of rays
+    let faint = Masked::new(sphere(), SolidColour::scalar(0.25), AlphaMode::Stochastic);
+    let n = 4000;
+    let hits = (0..n)
+        .filter(|_| faint.hit(&ray, 0.001, f64::INFINITY).is_some())
+        .count();
+    // Either surface can stop the ray, so it's hit with probability 1 - 0.75^2
+    let expected = 1.0 - 0.75 * 0.75;
+    assert!((hits as f64 / n as f64 - expected).abs() < 0.04);
+}
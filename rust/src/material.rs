@@ -1,27 +1,112 @@
 use rand::Rng;
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 use crate::colour::Colour;
 use crate::hittable::HitRecord;
+use crate::medium::Medium;
+use crate::microfacet::{fresnel_conductor, fresnel_dielectric, reflect, refract, Ggx};
 use crate::ray::Ray;
-use crate::vector::random_in_unit_sphere;
+use crate::spectrum::{Dispersion, D_LINE};
+use crate::thin_film::ThinFilm;
+use crate::utils::deg_to_rad;
+use crate::vector::{random_in_unit_sphere, Onb, Vector};
 
 pub trait Material: Send + Sync {
     fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)>;
+    fn name(&self) -> &'static str;
+
+    // BSDF times the cosine of the angle to the normal, for a direction chosen by light sampling
+    fn eval(&self, _ray: &Ray, _record: &HitRecord, _direction: Vector) -> Colour {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    // Solid angle density with which `scatter` picks the direction. Zero for specular materials,
+    // which light sampling cannot reach.
+    fn pdf(&self, _ray: &Ray, _record: &HitRecord, _direction: Vector) -> f64 {
+        return 0.0;
+    }
+
+    // What fills a closed object made of this material, for transmitting materials
+    fn medium(&self) -> Option<Medium> {
+        return None;
+    }
+
+    // Radiance given off by the surface itself
+    fn emitted(&self, _record: &HitRecord) -> Colour {
+        return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    // Whether scattering depends on the wavelength, so paths must pick one to follow
+    fn dispersive(&self) -> bool {
+        return false;
+    }
+
+    // Whether the surface only bounds a medium, letting light straight through it
+    fn invisible(&self) -> bool {
+        return false;
+    }
+
+    // Whether the colour varies too finely with wavelength to upsample from RGB. Such
+    // materials give the colour at the ray's wavelength, when it has one.
+    fn iridescent(&self) -> bool {
+        return false;
+    }
 }
 
+// Lambertian, or with a roughness, Oren and Nayar's model of a surface made of tiny diffuse
+// facets, which looks flatter and more dusty
 pub struct Diffuse {
     pub colour: Colour,
+    // Standard deviation of the facets' slopes, in degrees
+    pub sigma: f64,
 }
 
 impl Diffuse {
     pub fn new(colour: Colour) -> Arc<Diffuse> {
-        return Arc::new(Diffuse { colour });
+        return Arc::new(Diffuse { colour, sigma: 0.0 });
+    }
+
+    pub fn rough(colour: Colour, sigma: f64) -> Arc<Diffuse> {
+        return Arc::new(Diffuse { colour, sigma });
+    }
+
+    // Scales the Lambertian BRDF, using the qualitative form of Oren-Nayar
+    fn oren_nayar(&self, wo: Vector, wi: Vector, normal: Vector) -> f64 {
+        if self.sigma <= 0.0 {
+            return 1.0;
+        }
+        let sigma2 = deg_to_rad(self.sigma).powi(2);
+        let a = 1.0 - 0.5 * sigma2 / (sigma2 + 0.33);
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        let (cos_o, cos_i) = (
+            normal.dot(wo).clamp(0.0, 1.0),
+            normal.dot(wi).clamp(0.0, 1.0),
+        );
+        let sin_o = (1.0 - cos_o * cos_o).sqrt();
+        let sin_i = (1.0 - cos_i * cos_i).sqrt();
+        if sin_o < 1e-6 || sin_i < 1e-6 {
+            return a;
+        }
+        // Cosine of the azimuthal angle between the directions
+        let cos_phi = ((wo - normal * cos_o).dot(wi - normal * cos_i) / (sin_o * sin_i)).max(0.0);
+        // sin(max(theta_i, theta_o)) * tan(min(theta_i, theta_o))
+        let sin_alpha_tan_beta = if cos_i > cos_o {
+            sin_o * sin_i / cos_i
+        } else {
+            sin_i * sin_o / cos_o
+        };
+        return a + b * cos_phi * sin_alpha_tan_beta;
     }
 }
 
 impl Material for Diffuse {
-    fn scatter(&self, _ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
+    fn name(&self) -> &'static str {
+        return "diffuse";
+    }
+
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
         let mut scatter_direction = record.normal + random_in_unit_sphere().unit();
 
         // Catch degenerate scatter direction
@@ -30,7 +115,22 @@ impl Material for Diffuse {
         }
 
         let scattered = Ray::new(record.p, scatter_direction);
-        return Some((scattered, self.colour));
+        let roughness = self.oren_nayar(
+            -ray.direction.unit(),
+            scatter_direction.unit(),
+            record.normal,
+        );
+        return Some((scattered, self.colour * roughness));
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> Colour {
+        let roughness = self.oren_nayar(-ray.direction.unit(), direction.unit(), record.normal);
+        return self.colour * (roughness * self.pdf(ray, record, direction));
+    }
+
+    fn pdf(&self, _ray: &Ray, record: &HitRecord, direction: Vector) -> f64 {
+        // Scattering is cosine weighted, so matches the Lambertian BSDF exactly
+        return record.normal.dot(direction.unit()).max(0.0) / PI;
     }
 }
 
@@ -46,6 +146,10 @@ impl Metal {
 }
 
 impl Material for Metal {
+    fn name(&self) -> &'static str {
+        return "metal";
+    }
+
     fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
         let reflected = ray.direction.reflect(record.normal);
         let scattered = Ray::new(record.p, reflected + (random_in_unit_sphere() * self.fuzz));
@@ -58,13 +162,157 @@ impl Material for Metal {
     }
 }
 
+// Measured complex refractive indices, sampled at red, green and blue wavelengths
+#[derive(Clone, Copy)]
+pub enum MetalPreset {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+impl MetalPreset {
+    // Returns the real and imaginary parts of the refractive index
+    pub fn ior(&self) -> (Colour, Colour) {
+        return match self {
+            MetalPreset::Gold => (
+                Colour::new(0.143, 0.374, 1.442),
+                Colour::new(3.983, 2.385, 1.603),
+            ),
+            MetalPreset::Copper => (
+                Colour::new(0.200, 0.924, 1.102),
+                Colour::new(3.912, 2.452, 2.142),
+            ),
+            MetalPreset::Aluminium => (
+                Colour::new(1.657, 0.880, 0.521),
+                Colour::new(9.224, 6.270, 4.837),
+            ),
+            MetalPreset::Silver => (
+                Colour::new(0.155, 0.117, 0.138),
+                Colour::new(4.828, 3.122, 2.147),
+            ),
+        };
+    }
+}
+
+// Rough metal with a GGX microfacet BRDF and exact conductor Fresnel
+pub struct Conductor {
+    pub eta: Colour,
+    pub k: Colour,
+    pub distribution: Ggx,
+    pub film: Option<ThinFilm>,
+}
+
+impl Conductor {
+    // Roughness can differ along the surface's two tangent directions for brushed metals
+    pub fn new(eta: Colour, k: Colour, roughness_u: f64, roughness_v: f64) -> Arc<Conductor> {
+        return Arc::new(Conductor {
+            eta,
+            k,
+            distribution: Ggx::new(roughness_u, roughness_v),
+            film: None,
+        });
+    }
+
+    pub fn preset(preset: MetalPreset, roughness_u: f64, roughness_v: f64) -> Arc<Conductor> {
+        let (eta, k) = preset.ior();
+        return Conductor::new(eta, k, roughness_u, roughness_v);
+    }
+
+    // A metal under a thin transparent film, like oxidised titanium or oil on steel
+    pub fn with_film(
+        preset: MetalPreset,
+        roughness_u: f64,
+        roughness_v: f64,
+        film: ThinFilm,
+    ) -> Arc<Conductor> {
+        let (eta, k) = preset.ior();
+        return Arc::new(Conductor {
+            eta,
+            k,
+            distribution: Ggx::new(roughness_u, roughness_v),
+            film: Some(film),
+        });
+    }
+
+    fn fresnel(&self, cos_theta: f64, wavelength: Option<f64>) -> Colour {
+        return match self.film {
+            Some(film) => film.reflectance_rgb(cos_theta, 1.0, self.eta, self.k, wavelength),
+            None => fresnel_conductor(cos_theta, self.eta, self.k),
+        };
+    }
+}
+
+impl Material for Conductor {
+    fn name(&self) -> &'static str {
+        return "conductor";
+    }
+
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
+        let frame = Onb::from_tangent(record.normal, record.dpdu);
+        let wo = frame.to_local(-ray.direction.unit());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let mut rng = rand::thread_rng();
+        let m = self.distribution.sample_visible(wo, rng.gen(), rng.gen());
+        let wi = reflect(wo, m);
+        // Light reflected below the surface would scatter again between microfacets, which
+        // this single scattering model leaves out
+        if wi.z() <= 0.0 {
+            return None;
+        }
+        // With visible normal sampling, D and the cosines cancel out of the weight
+        let weight = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        let f = self.fresnel(wo.dot(m), ray.wavelength);
+        return Some((Ray::new(record.p, frame.to_world(wi)), f * weight));
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> Colour {
+        let frame = Onb::from_tangent(record.normal, record.dpdu);
+        let wo = frame.to_local(-ray.direction.unit());
+        let wi = frame.to_local(direction.unit());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Colour::new(0.0, 0.0, 0.0);
+        }
+        let m = (wo + wi).unit();
+        let f = self.fresnel(wi.dot(m), ray.wavelength);
+        return f * (self.distribution.d(m) * self.distribution.g(wo, wi) / (4.0 * wo.z()));
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> f64 {
+        let frame = Onb::from_tangent(record.normal, record.dpdu);
+        let wo = frame.to_local(-ray.direction.unit());
+        let wi = frame.to_local(direction.unit());
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let m = (wo + wi).unit();
+        // Jacobian of the reflection from the half vector to the outgoing direction
+        return self.distribution.pdf_visible(wo, m) / (4.0 * wo.dot(m));
+    }
+
+    fn iridescent(&self) -> bool {
+        return self.film.is_some();
+    }
+}
+
 pub struct Glass {
     pub refractive_idx: f64,
+    pub interior: Option<Medium>,
+    // Overrides the refractive index with one that varies by wavelength, splitting white light
+    pub dispersion: Option<Dispersion>,
+    pub film: Option<ThinFilm>,
 }
 
 impl Glass {
     pub fn new(refractive_idx: f64) -> Arc<Glass> {
-        return Arc::new(Glass { refractive_idx });
+        return Arc::new(Glass {
+            refractive_idx,
+            interior: None,
+            dispersion: None,
+            film: None,
+        });
     }
 
     fn reflectance(cosine: f64, refractive_idx: f64) -> f64 {
@@ -74,29 +322,310 @@ impl Glass {
 }
 
 impl Material for Glass {
+    fn name(&self) -> &'static str {
+        return "glass";
+    }
+
     fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
         let unit_direction = ray.direction.unit();
 
         let cos_theta = (-unit_direction).dot(record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).powf(0.5);
-        let refractive_idx = if record.front_face {
-            1.0 / self.refractive_idx
+        let ior = match self.dispersion {
+            Some(dispersion) => dispersion.ior(ray.wavelength.unwrap_or(D_LINE)),
+            None => self.refractive_idx,
+        };
+        let refractive_idx = if record.front_face { 1.0 / ior } else { ior };
+        let cannot_refract = refractive_idx * sin_theta > 1.0;
+
+        // A film's reflectance is coloured, so rays reflect with its average and are
+        // weighted by how that differs from each channel
+        let (reflectance, weight) = match self.film {
+            Some(film) => {
+                let (outside, inside) = if record.front_face {
+                    (1.0, ior)
+                } else {
+                    (ior, 1.0)
+                };
+                let black = Colour::new(0.0, 0.0, 0.0);
+                let r = film.reflectance_rgb(
+                    cos_theta,
+                    outside,
+                    Colour::new(inside, inside, inside),
+                    black,
+                    ray.wavelength,
+                );
+                let p = ((r.r + r.g + r.b) / 3.0).clamp(1e-3, 1.0 - 1e-3);
+                (p, Some((r, p)))
+            }
+            None => (Self::reflectance(cos_theta, refractive_idx), None),
+        };
+        let will_reflect = reflectance > rand::thread_rng().gen();
+
+        let white = Colour::new(1.0, 1.0, 1.0);
+        let (direction, attenuation) = if cannot_refract {
+            (unit_direction.reflect(record.normal), white)
+        } else if will_reflect {
+            let attenuation = match weight {
+                Some((r, p)) => r * (1.0 / p),
+                None => white,
+            };
+            (unit_direction.reflect(record.normal), attenuation)
         } else {
-            self.refractive_idx
+            let attenuation = match weight {
+                Some((r, p)) => Colour::new(1.0 - r.r, 1.0 - r.g, 1.0 - r.b) * (1.0 / (1.0 - p)),
+                None => white,
+            };
+            (
+                unit_direction.refract(record.normal, refractive_idx),
+                attenuation,
+            )
         };
 
-        let cannot_refract = refractive_idx * sin_theta > 1.0;
-        let will_reflect = Self::reflectance(cos_theta, refractive_idx) > rand::thread_rng().gen();
+        return Some((Ray::new(record.p, direction), attenuation));
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        return self.interior.clone();
+    }
+
+    fn dispersive(&self) -> bool {
+        return self.dispersion.is_some();
+    }
+
+    fn iridescent(&self) -> bool {
+        return self.film.is_some();
+    }
+}
+
+// Frosted glass, with a GGX microfacet BSDF that both reflects and transmits
+// (Walter et al., "Microfacet Models for Refraction through Rough Surfaces", 2007)
+pub struct RoughGlass {
+    pub refractive_idx: f64,
+    pub distribution: Ggx,
+    pub interior: Option<Medium>,
+}
+
+impl RoughGlass {
+    pub fn new(refractive_idx: f64, roughness: f64) -> Arc<RoughGlass> {
+        return Arc::new(RoughGlass {
+            refractive_idx,
+            distribution: Ggx::new(roughness, roughness),
+            interior: None,
+        });
+    }
+
+    pub fn with_medium(refractive_idx: f64, roughness: f64, interior: Medium) -> Arc<RoughGlass> {
+        return Arc::new(RoughGlass {
+            refractive_idx,
+            distribution: Ggx::new(roughness, roughness),
+            interior: Some(interior),
+        });
+    }
+
+    // Ratio of the refractive index on the far side of the surface to the one the ray is in.
+    // The record's normal always faces the ray, so only this depends on which side it's on.
+    fn eta(&self, record: &HitRecord) -> f64 {
+        if record.front_face {
+            return self.refractive_idx;
+        }
+        return 1.0 / self.refractive_idx;
+    }
+
+    // Returns the BSDF times the cosine and the pdf of sampling the direction, together
+    fn evaluate(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> (Colour, f64) {
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let frame = Onb::from_w(record.normal);
+        let wo = frame.to_local(-ray.direction.unit());
+        let wi = frame.to_local(direction.unit());
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return (black, 0.0);
+        }
+        let distribution = &self.distribution;
+        let eta = self.eta(record);
+
+        if wi.z() > 0.0 {
+            let m = (wo + wi).unit();
+            let f = fresnel_dielectric(wo.dot(m), eta);
+            let value = f * distribution.d(m) * distribution.g(wo, wi) / (4.0 * wo.z());
+            let pdf = f * distribution.pdf_visible(wo, m) / (4.0 * wo.dot(m));
+            return (Colour::new(value, value, value), pdf);
+        }
+
+        // The half vector of a refraction, turned to face the ray's side
+        let mut m = -(wo + wi * eta).unit();
+        if m.z() < 0.0 {
+            m = -m;
+        }
+        let (cos_o, cos_i) = (wo.dot(m), wi.dot(m));
+        if cos_o <= 0.0 || cos_i >= 0.0 {
+            return (black, 0.0);
+        }
+        let f = fresnel_dielectric(cos_o, eta);
+        // Jacobian of the refraction from the half vector to the outgoing direction
+        let jacobian = eta * eta * cos_i.abs() / (cos_o + eta * cos_i).powi(2);
+        let value =
+            (1.0 - f) * distribution.d(m) * distribution.g(wo, wi) * cos_o * jacobian / wo.z();
+        let pdf = (1.0 - f) * distribution.pdf_visible(wo, m) * jacobian;
+        return (Colour::new(value, value, value), pdf);
+    }
+}
+
+impl Material for RoughGlass {
+    fn name(&self) -> &'static str {
+        return "rough glass";
+    }
+
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
+        let frame = Onb::from_w(record.normal);
+        let wo = frame.to_local(-ray.direction.unit());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let mut rng = rand::thread_rng();
+        let m = self.distribution.sample_visible(wo, rng.gen(), rng.gen());
+        let f = fresnel_dielectric(wo.dot(m), self.eta(record));
 
-        let direction = if cannot_refract || will_reflect {
-            // Reflect
-            unit_direction.reflect(record.normal)
+        // Reflects or refracts with the Fresnel probability, which cancels out of the weight
+        let wi = if rng.gen::<f64>() < f {
+            reflect(wo, m)
         } else {
-            // Refract
-            unit_direction.refract(record.normal, refractive_idx)
+            refract(wo, m, self.eta(record))?
         };
+        let reflected = wo.dot(m) * wi.dot(m) > 0.0;
+        if (wi.z() > 0.0) != reflected || wi.z() == 0.0 {
+            return None;
+        }
+        let weight = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        return Some((
+            Ray::new(record.p, frame.to_world(wi)),
+            Colour::new(weight, weight, weight),
+        ));
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> Colour {
+        return self.evaluate(ray, record, direction).0;
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> f64 {
+        return self.evaluate(ray, record, direction).1;
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        return self.interior.clone();
+    }
+}
+
+// Translucent materials such as wax, skin and marble, where light goes under the surface and
+// scatters about before coming out again, often somewhere else. The surface is rough glass,
+// and the scattering medium within it is followed by the integrator's random walk, so objects
+// made of it must be closed.
+pub struct Subsurface {
+    pub boundary: RoughGlass,
+    pub interior: Medium,
+}
+
+impl Subsurface {
+    // The albedo is the colour of a thick slab, and the mean free path how far light travels
+    // between scattering events in each channel, so how far it bleeds under the surface
+    pub fn new(
+        albedo: Colour,
+        mean_free_path: Colour,
+        refractive_idx: f64,
+        roughness: f64,
+    ) -> Arc<Subsurface> {
+        return Arc::new(Subsurface {
+            boundary: RoughGlass {
+                refractive_idx,
+                distribution: Ggx::new(roughness, roughness),
+                interior: None,
+            },
+            interior: Medium::subsurface(albedo, mean_free_path, 0.0),
+        });
+    }
+}
+
+impl Material for Subsurface {
+    fn name(&self) -> &'static str {
+        return "subsurface";
+    }
+
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
+        return self.boundary.scatter(ray, record);
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> Colour {
+        return self.boundary.eval(ray, record, direction);
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> f64 {
+        return self.boundary.pdf(ray, record, direction);
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        return Some(self.interior.clone());
+    }
+}
 
-        return Some((Ray::new(record.p, direction), Colour::new(1.0, 1.0, 1.0)));
+#[test]
+fn test_rough_glass() {
+    use crate::point::Point;
+
+    // Sampled weights must agree with eval over pdf for light sampling to be unbiased,
+    // both entering the glass and leaving it
+    let glass = RoughGlass::new(1.5, 0.4);
+    let normal = Vector::new(0.0, 1.0, 0.0);
+    for direction in [Vector::new(0.5, -1.0, 0.2), Vector::new(0.5, 1.0, 0.2)] {
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0) - direction, direction);
+        let record =
+            HitRecord::new_from_ray(Point::new(0.0, 0.0, 0.0), normal, 1.0, &ray, glass.clone());
+        let (mut reflected, mut refracted) = (0, 0);
+        for _ in 0..200 {
+            let (scattered, weight) = match glass.scatter(&ray, &record) {
+                Some(s) => s,
+                None => continue,
+            };
+            let (f, pdf) = glass.evaluate(&ray, &record, scattered.direction);
+            assert!((f.r / pdf - weight.r).abs() < 1e-6 * weight.r.max(1.0));
+            if scattered.direction.dot(record.normal) > 0.0 {
+                reflected += 1;
+            } else {
+                refracted += 1;
+            }
+        }
+        assert!(reflected > 0 && refracted > reflected);
+    }
+}
+
+#[test]
+fn test_oren_nayar() {
+    use crate::point::Point;
+
+    let normal = Vector::new(0.0, 1.0, 0.0);
+    let direction = Vector::new(1.0, -1.0, 0.0);
+    let ray = Ray::new(Point::new(0.0, 0.0, 0.0) - direction, direction);
+    let smooth = Diffuse::new(Colour::new(1.0, 1.0, 1.0));
+    let rough = Diffuse::rough(Colour::new(1.0, 1.0, 1.0), 30.0);
+    let record =
+        HitRecord::new_from_ray(Point::new(0.0, 0.0, 0.0), normal, 1.0, &ray, smooth.clone());
+
+    // Rough surfaces are darker head on, and brighter back towards the light
+    let up = Vector::new(0.0, 1.0, 0.0);
+    assert!(rough.eval(&ray, &record, up).r < smooth.eval(&ray, &record, up).r);
+    let back = Vector::new(-1.0, 0.5, 0.0);
+    assert!(rough.eval(&ray, &record, back).r > smooth.eval(&ray, &record, back).r);
+    assert_eq!(
+        smooth.oren_nayar(-direction.unit(), back.unit(), normal),
+        1.0
+    );
+
+    for _ in 0..50 {
+        let (scattered, weight) = rough.scatter(&ray, &record).unwrap();
+        let f = rough.eval(&ray, &record, scattered.direction);
+        let pdf = rough.pdf(&ray, &record, scattered.direction);
+        if pdf > 1e-6 {
+            assert!((f.r / pdf - weight.r).abs() < 1e-9);
+        }
     }
 }
 
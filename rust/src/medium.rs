@@ -0,0 +1,218 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::point::Point;
+use crate::vector::{Onb, Vector};
+use crate::volume::Density;
+
+// The interior of a closed object, which light passes through on its way between surfaces.
+// Media that scatter as well as absorb are followed by a random walk, bouncing from particle
+// to particle until the light finds its way out, which is what makes wax, skin and marble
+// look soft and glow where they're thin. With a density, the medium varies through space, as
+// in smoke and clouds, and its coefficients are the ones where the density is one.
+#[derive(Clone)]
+pub struct Medium {
+    // Fraction of light absorbed per unit distance, for each channel
+    pub absorption: Colour,
+    // Fraction of light scattered in a new direction per unit distance, for each channel
+    pub scattering: Colour,
+    // Mean cosine of the angle light is scattered through, from -1 for straight back, through
+    // 0 for evenly in every direction, to 1 for straight on
+    pub anisotropy: f64,
+    pub density: Option<Arc<dyn Density>>,
+    // Radiance given off per unit distance, as by fire
+    pub emission: Colour,
+}
+
+impl Medium {
+    pub fn new(absorption: Colour) -> Medium {
+        return Medium {
+            absorption,
+            scattering: Colour::new(0.0, 0.0, 0.0),
+            anisotropy: 0.0,
+            density: None,
+            emission: Colour::new(0.0, 0.0, 0.0),
+        };
+    }
+
+    pub fn heterogeneous(
+        density: Arc<dyn Density>,
+        absorption: Colour,
+        scattering: Colour,
+    ) -> Medium {
+        return Medium {
+            scattering,
+            density: Some(density),
+            ..Medium::new(absorption)
+        };
+    }
+
+    // A scattering medium which, when thick, reflects the given albedo from its surface, and
+    // where light travels the given mean distance between events in each channel. The single
+    // scattering albedo that gives the surface albedo after many bounces is from Chiang et
+    // al., "Practical and Controllable Subsurface Scattering for Production Path Tracing", 2016.
+    pub fn subsurface(albedo: Colour, mean_free_path: Colour, anisotropy: f64) -> Medium {
+        let single = |a: f64| -> f64 {
+            let a = a.clamp(0.0, 1.0);
+            let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+            return 1.0 - s * s;
+        };
+        let extinction = |d: f64| -> f64 { 1.0 / d.max(1e-6) };
+        let sigma_t = Colour::new(
+            extinction(mean_free_path.r),
+            extinction(mean_free_path.g),
+            extinction(mean_free_path.b),
+        );
+        let alpha = Colour::new(single(albedo.r), single(albedo.g), single(albedo.b));
+        let scattering = sigma_t * alpha;
+        return Medium {
+            scattering,
+            anisotropy,
+            ..Medium::new(Colour::new(
+                sigma_t.r - scattering.r,
+                sigma_t.g - scattering.g,
+                sigma_t.b - scattering.b,
+            ))
+        };
+    }
+
+    pub fn scatters(&self) -> bool {
+        return self.scattering.r > 0.0 || self.scattering.g > 0.0 || self.scattering.b > 0.0;
+    }
+
+    pub fn emits(&self) -> bool {
+        return self.emission.r > 0.0 || self.emission.g > 0.0 || self.emission.b > 0.0;
+    }
+
+    // Whether light only fades through the medium, by the same amount along any path of the
+    // same length, so it needn't be tracked through it
+    pub fn absorbs_only(&self) -> bool {
+        return self.density.is_none() && !self.scatters() && !self.emits();
+    }
+
+    // Density at a point in world space, at most max_density()
+    pub fn density_at(&self, p: Point) -> f64 {
+        return match &self.density {
+            Some(density) => density.density(p).clamp(0.0, density.max_density()),
+            None => 1.0,
+        };
+    }
+
+    pub fn max_density(&self) -> f64 {
+        return self
+            .density
+            .as_ref()
+            .map_or(1.0, |density| density.max_density());
+    }
+
+    // Fraction of light either absorbed or scattered per unit distance
+    pub fn extinction(&self) -> Colour {
+        return self.absorption + self.scattering;
+    }
+
+    // Fraction of the light meeting a particle that's scattered rather than absorbed
+    pub fn albedo(&self) -> Colour {
+        let ratio = |s: f64, t: f64| -> f64 {
+            if t > 0.0 {
+                return s / t;
+            }
+            return 0.0;
+        };
+        let sigma_t = self.extinction();
+        return Colour::new(
+            ratio(self.scattering.r, sigma_t.r),
+            ratio(self.scattering.g, sigma_t.g),
+            ratio(self.scattering.b, sigma_t.b),
+        );
+    }
+
+    // Henyey-Greenstein phase function, giving the density of scattering from the direction
+    // of travel into another, over solid angle
+    pub fn phase(&self, direction: Vector, scattered: Vector) -> f64 {
+        let g = self.anisotropy;
+        let cos_theta = direction.unit().dot(scattered.unit());
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+        return (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt());
+    }
+
+    // Picks a scattered direction exactly in proportion to the phase function
+    pub fn sample_phase(&self, direction: Vector, u1: f64, u2: f64) -> Vector {
+        let g = self.anisotropy;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u1
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u1);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        let local = Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        return Onb::from_w(direction.unit()).to_world(local);
+    }
+
+    // Absorbs just enough to leave the given colour after light travels the given distance
+    pub fn from_colour(colour: Colour, distance: f64) -> Medium {
+        let coefficient = |c: f64| -> f64 { -c.max(1e-6).ln() / distance };
+        return Medium::new(Colour::new(
+            coefficient(colour.r),
+            coefficient(colour.g),
+            coefficient(colour.b),
+        ));
+    }
+
+    // Beer-Lambert attenuation over a straight path through a homogeneous medium, of light
+    // that's neither absorbed nor scattered away
+    pub fn transmittance(&self, distance: f64) -> Colour {
+        let sigma_t = self.extinction();
+        return Colour::new(
+            (-sigma_t.r * distance).exp(),
+            (-sigma_t.g * distance).exp(),
+            (-sigma_t.b * distance).exp(),
+        );
+    }
+}
+
+#[test]
+fn test_medium() {
+    let medium = Medium::from_colour(Colour::new(0.5, 1.0, 0.25), 2.0);
+    let t = medium.transmittance(2.0);
+    assert!((t.r - 0.5).abs() < 1e-12 && t.g == 1.0 && (t.b - 0.25).abs() < 1e-12);
+    let t = medium.transmittance(4.0);
+    assert!((t.r - 0.25).abs() < 1e-12);
+    assert!(!medium.scatters() && medium.absorbs_only());
+
+    // Black scatters nothing and white absorbs almost nothing, with the extinction set by the
+    // mean free path either way
+    let wax = Medium::subsurface(Colour::new(0.0, 0.5, 1.0), Colour::new(0.5, 0.1, 0.1), 0.0);
+    assert!(wax.scattering.r.abs() < 1e-4 && (wax.extinction().r - 2.0).abs() < 1e-9);
+    assert!(wax.absorption.b < 0.01 && (wax.extinction().b - 10.0).abs() < 1e-9);
+    assert!(wax.scatters() && wax.scattering.g > 0.0 && wax.absorption.g > 0.0);
+
+    // Sampled directions follow the phase function, whose mean cosine is the anisotropy
+    let n = 20000;
+    let forward = Vector::new(0.0, 0.0, 1.0);
+    for g in [-0.5, 0.0, 0.8] {
+        let medium = Medium {
+            anisotropy: g,
+            ..wax.clone()
+        };
+        let mean = (0..n)
+            .map(|i| {
+                let u1 = (i as f64 + 0.5) / n as f64;
+                medium.sample_phase(forward, u1, 0.37).dot(forward)
+            })
+            .sum::<f64>()
+            / n as f64;
+        assert!((mean - g).abs() < 1e-3);
+        // And the phase function integrates to one over the sphere
+        let total = (0..n)
+            .map(|i| {
+                let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / n as f64;
+                let d = Vector::new((1.0 - cos_theta * cos_theta).sqrt(), 0.0, cos_theta);
+                medium.phase(forward, d) * 2.0 * PI * 2.0 / n as f64
+            })
+            .sum::<f64>();
+        assert!((total - 1.0).abs() < 1e-3);
+    }
+}
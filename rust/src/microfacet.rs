@@ -0,0 +1,187 @@
+use std::f64::consts::PI;
+
+use crate::colour::Colour;
+use crate::vector::Vector;
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals, in a local frame with the
+// surface normal along +z and the x axis along the first roughness direction
+#[derive(Clone, Copy)]
+pub struct Ggx {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Ggx {
+    // Takes perceptual roughness, which is squared to give the distribution's width
+    pub fn new(roughness_x: f64, roughness_y: f64) -> Ggx {
+        return Ggx {
+            alpha_x: (roughness_x * roughness_x).max(1e-4),
+            alpha_y: (roughness_y * roughness_y).max(1e-4),
+        };
+    }
+
+    pub fn d(&self, m: Vector) -> f64 {
+        if m.z() <= 0.0 {
+            return 0.0;
+        }
+        let (ax, ay) = (self.alpha_x, self.alpha_y);
+        let e = (m.x() / ax).powi(2) + (m.y() / ay).powi(2) + m.z() * m.z();
+        return 1.0 / (PI * ax * ay * e * e);
+    }
+
+    fn lambda(&self, w: Vector) -> f64 {
+        if w.z() == 0.0 {
+            return f64::INFINITY;
+        }
+        let t = ((self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2)) / (w.z() * w.z());
+        return 0.5 * (-1.0 + (1.0 + t).sqrt());
+    }
+
+    // Fraction of microfacets facing w that aren't masked from it
+    pub fn g1(&self, w: Vector) -> f64 {
+        return 1.0 / (1.0 + self.lambda(w));
+    }
+
+    // Height-correlated masking and shadowing
+    pub fn g(&self, wo: Vector, wi: Vector) -> f64 {
+        return 1.0 / (1.0 + self.lambda(wo) + self.lambda(wi));
+    }
+
+    // Samples a microfacet normal in proportion to how much of it is visible from wo
+    // (Heitz, "Sampling the GGX Distribution of Visible Normals", 2018)
+    pub fn sample_visible(&self, wo: Vector, u1: f64, u2: f64) -> Vector {
+        let (ax, ay) = (self.alpha_x, self.alpha_y);
+        // Stretches the view direction to the hemisphere configuration
+        let vh = Vector::new(ax * wo.x(), ay * wo.y(), wo.z()).unit();
+        let len_sq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if len_sq > 0.0 {
+            Vector::new(-vh.y(), vh.x(), 0.0) / len_sq.sqrt()
+        } else {
+            Vector::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        // Samples the projected area of the hemisphere as seen from vh
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+        // Unstretches back to the ellipsoid configuration
+        return Vector::new(ax * nh.x(), ay * nh.y(), nh.z().max(0.0)).unit();
+    }
+
+    // Density of `sample_visible` choosing m
+    pub fn pdf_visible(&self, wo: Vector, m: Vector) -> f64 {
+        if wo.z() <= 0.0 {
+            return 0.0;
+        }
+        return self.g1(wo) * wo.dot(m).max(0.0) * self.d(m) / wo.z();
+    }
+}
+
+// Exact Fresnel reflectance of unpolarised light from a conductor with complex refractive
+// index eta + ik, computed per channel
+pub fn fresnel_conductor(cos_theta: f64, eta: Colour, k: Colour) -> Colour {
+    let channel = |eta: f64, k: f64| -> f64 {
+        let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_theta.clamp(0.0, 1.0) * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        return 0.5 * (rp + rs);
+    };
+    return Colour::new(
+        channel(eta.r, k.r),
+        channel(eta.g, k.g),
+        channel(eta.b, k.b),
+    );
+}
+
+// Exact Fresnel reflectance of unpolarised light at a dielectric boundary, where eta is the
+// ratio of the refractive index beyond the boundary to the one before it
+pub fn fresnel_dielectric(cos_theta: f64, eta: f64) -> f64 {
+    let cos_i = cos_theta.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        // Total internal reflection
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    return 0.5 * (rs * rs + rp * rp);
+}
+
+pub fn reflect(wo: Vector, m: Vector) -> Vector {
+    return m * (2.0 * wo.dot(m)) - wo;
+}
+
+// Refracts wo through the microfacet m, or returns None on total internal reflection
+pub fn refract(wo: Vector, m: Vector, eta: f64) -> Option<Vector> {
+    let cos_i = wo.dot(m);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    return Some(-wo / eta + m * (cos_i / eta - cos_t));
+}
+
+#[test]
+fn test_ggx() {
+    use rand::Rng;
+
+    // Visible normals are always in front of the viewer
+    let ggx = Ggx::new(0.5, 0.2);
+    let wo = Vector::new(0.6, 0.3, 0.2).unit();
+    let mut rng = rand::thread_rng();
+    for _ in 0..100 {
+        let m = ggx.sample_visible(wo, rng.gen(), rng.gen());
+        assert!(m.z() >= 0.0 && wo.dot(m) >= -1e-9);
+    }
+
+    // Estimates the integral of the visible normal pdf over the hemisphere, which should be one
+    let ggx = Ggx::new(0.9, 0.7);
+    let n = 400;
+    let mut total = 0.0;
+    for i in 0..n {
+        for j in 0..n {
+            let cos_theta = (i as f64 + 0.5) / n as f64;
+            let phi = 2.0 * PI * (j as f64 + 0.5) / n as f64;
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let m = Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+            total += ggx.pdf_visible(wo, m) * 2.0 * PI / (n * n) as f64;
+        }
+    }
+    assert!((total - 1.0).abs() < 0.01);
+
+    // Gold reflects red more than blue, and everything at grazing angles
+    let gold = fresnel_conductor(
+        1.0,
+        Colour::new(0.143, 0.374, 1.442),
+        Colour::new(3.983, 2.385, 1.603),
+    );
+    assert!(gold.r > 0.9 && gold.b < 0.5);
+    let grazing = fresnel_conductor(
+        0.0,
+        Colour::new(0.143, 0.374, 1.442),
+        Colour::new(3.983, 2.385, 1.603),
+    );
+    assert!((grazing.b - 1.0).abs() < 1e-9);
+
+    // Glass reflects 4% head on, and everything past the critical angle from inside
+    assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-9);
+    assert_eq!(fresnel_dielectric(0.5, 1.0 / 1.5), 1.0);
+    let m = Vector::new(0.0, 0.0, 1.0);
+    let wi = refract(Vector::new(0.6, 0.0, 0.8), m, 1.5).unwrap();
+    assert!((wi.length() - 1.0).abs() < 1e-12 && (wi.x() * 1.5 + 0.6).abs() < 1e-12);
+}
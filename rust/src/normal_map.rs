@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use crate::hittable::{Hit, HitRecord, Interval};
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vector::{Onb, Vector};
+
+// Step in (u, v) used to take the slope of height maps
+const BUMP_DELTA: f64 = 1e-4;
+
+pub enum SurfaceMap {
+    // Tangent space normals encoded as colours, with blue along the surface normal and red
+    // along the direction of increasing u
+    Normal(Arc<dyn Texture>),
+    // Heights read from any texture's red channel, with strength scaling the slopes
+    Bump {
+        height: Arc<dyn Texture>,
+        strength: f64,
+    },
+}
+
+// Perturbs an object's shading normals, leaving its geometry alone
+pub struct NormalMapped {
+    object: Box<dyn Hit>,
+    map: SurfaceMap,
+}
+
+impl NormalMapped {
+    pub fn new(object: impl Hit + 'static, map: SurfaceMap) -> NormalMapped {
+        return NormalMapped {
+            object: Box::new(object),
+            map,
+        };
+    }
+}
+
+impl NormalMapped {
+    fn perturb(&self, ray: &Ray, mut rec: HitRecord) -> HitRecord {
+        let normal = match &self.map {
+            SurfaceMap::Normal(texture) => {
+                let c = texture.value(rec.u, rec.v, rec.p);
+                let frame = Onb::from_tangent(rec.normal, rec.dpdu);
+                frame.to_world(Vector::new(
+                    2.0 * c.r - 1.0,
+                    2.0 * c.g - 1.0,
+                    2.0 * c.b - 1.0,
+                ))
+            }
+            SurfaceMap::Bump { height, strength } => {
+                // Displaces the surface along its normal, and takes the new surface's normal
+                let h = |du: f64, dv: f64| -> f64 {
+                    let p = rec.p + rec.dpdu * du + rec.dpdv * dv;
+                    return height.value(rec.u + du, rec.v + dv, p).r * strength;
+                };
+                let base = h(0.0, 0.0);
+                let dpdu = rec.dpdu + rec.normal * ((h(BUMP_DELTA, 0.0) - base) / BUMP_DELTA);
+                let dpdv = rec.dpdv + rec.normal * ((h(0.0, BUMP_DELTA) - base) / BUMP_DELTA);
+                dpdu.cross(dpdv)
+            }
+        };
+        if !normal.near_zero() {
+            rec.set_shading_normal(ray, normal);
+        }
+        return rec;
+    }
+}
+
+impl Hit for NormalMapped {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let rec = self.object.hit(ray, t_min, t_max)?;
+        return Some(self.perturb(ray, rec));
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        return self
+            .object
+            .intervals(ray)
+            .into_iter()
+            .map(|interval| Interval {
+                enter: self.perturb(ray, interval.enter),
+                exit: self.perturb(ray, interval.exit),
+            })
+            .collect();
+    }
+}
+
+#[test]
+fn test_normal_map() {
+    use crate::colour::Colour;
+    use crate::material::Diffuse;
+    use crate::point::Point;
+    use crate::sphere::Sphere;
+    use crate::texture::SolidColour;
+
+    let sphere = || {
+        Sphere::new(
+            Point::new(0.0, 0.0, 0.0),
+            1.0,
+            Diffuse::new(Colour::new(0.5, 0.5, 0.5)),
+        )
+    };
+    let ray = Ray::new(Point::new(5.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0));
+
+    // A flat normal map and a constant height leave the normal alone
+    let flat = NormalMapped::new(
+        sphere(),
+        SurfaceMap::Normal(SolidColour::new(Colour::new(0.5, 0.5, 1.0))),
+    );
+    let rec = flat.hit(&ray, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.normal.x() - 1.0).abs() < 1e-9);
+    let level = NormalMapped::new(
+        sphere(),
+        SurfaceMap::Bump {
+            height: SolidColour::scalar(0.3),
+            strength: 1.0,
+        },
+    );
+    let rec = level.hit(&ray, 0.001, f64::INFINITY).unwrap();
+    assert!((rec.normal.x() - 1.0).abs() < 1e-9);
+
+    // Tilting towards +u turns the normal along the tangent, keeping the geometric normal
+    let tilted = NormalMapped::new(
+        sphere(),
+        SurfaceMap::Normal(SolidColour::new(Colour::new(1.0, 0.5, 0.5))),
+    );
+    let rec = tilted.hit(&ray, 0.001, f64::INFINITY).unwrap();
+    assert!(rec.normal.dot(rec.dpdu.unit()) > 0.5);
+    assert!(rec.normal.dot(-ray.direction) > 0.0);
+    assert_eq!(rec.geometric_normal.x(), 1.0);
+}
@@ -0,0 +1,326 @@
+use rand::Rng;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::microfacet::{fresnel_dielectric, reflect, refract, Ggx};
+use crate::ray::Ray;
+use crate::texture::{SolidColour, Texture};
+use crate::vector::{Onb, Vector};
+
+// Disney-style uber material, after Burley's "Physically Based Shading at Disney" (2012) and
+// "Extending the Disney BRDF to a BSDF with Integrated Subsurface Scattering" (2015). Every
+// parameter but the index of refraction is a texture, with scalars read from the red channel.
+// Start from `new` and override fields with struct update syntax.
+pub struct Principled {
+    pub base_colour: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    // Scales the dielectric reflectance at normal incidence, where 0.5 gives 4%
+    pub specular: Arc<dyn Texture>,
+    pub specular_tint: Arc<dyn Texture>,
+    pub sheen: Arc<dyn Texture>,
+    pub sheen_tint: Arc<dyn Texture>,
+    pub clearcoat: Arc<dyn Texture>,
+    pub clearcoat_gloss: Arc<dyn Texture>,
+    pub transmission: Arc<dyn Texture>,
+    pub emission: Arc<dyn Texture>,
+    pub ior: f64,
+}
+
+// Parameters looked up at a hit
+struct Parameters {
+    base_colour: Colour,
+    metallic: f64,
+    roughness: f64,
+    specular_f0: Colour,
+    sheen: Colour,
+    clearcoat: f64,
+    specular: Ggx,
+    clearcoat_alpha: f64,
+    transmission: f64,
+}
+
+impl Principled {
+    // A rough white plastic, or the given colour
+    pub fn new(base_colour: Arc<dyn Texture>) -> Principled {
+        return Principled {
+            base_colour,
+            metallic: SolidColour::scalar(0.0),
+            roughness: SolidColour::scalar(0.5),
+            specular: SolidColour::scalar(0.5),
+            specular_tint: SolidColour::scalar(0.0),
+            sheen: SolidColour::scalar(0.0),
+            sheen_tint: SolidColour::scalar(0.5),
+            clearcoat: SolidColour::scalar(0.0),
+            clearcoat_gloss: SolidColour::scalar(1.0),
+            transmission: SolidColour::scalar(0.0),
+            emission: SolidColour::scalar(0.0),
+            ior: 1.5,
+        };
+    }
+
+    fn parameters(&self, record: &HitRecord) -> Parameters {
+        let lookup = |texture: &Arc<dyn Texture>| texture.value(record.u, record.v, record.p);
+        let scalar = |texture: &Arc<dyn Texture>| lookup(texture).r.clamp(0.0, 1.0);
+
+        let base_colour = lookup(&self.base_colour);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let white = Colour::new(1.0, 1.0, 1.0);
+        // The base colour's hue and saturation, without its brightness
+        let tint = if base_colour.luminance() > 0.0 {
+            base_colour * (1.0 / base_colour.luminance())
+        } else {
+            white
+        };
+        let dielectric_f0 =
+            white.interpolate(tint, scalar(&self.specular_tint)) * (0.08 * scalar(&self.specular));
+        return Parameters {
+            base_colour,
+            metallic,
+            roughness,
+            specular_f0: dielectric_f0.interpolate(base_colour, metallic),
+            sheen: white.interpolate(tint, scalar(&self.sheen_tint)) * scalar(&self.sheen),
+            clearcoat: scalar(&self.clearcoat),
+            specular: Ggx::new(roughness, roughness),
+            clearcoat_alpha: 0.1 + (0.001 - 0.1) * scalar(&self.clearcoat_gloss),
+            transmission: scalar(&self.transmission),
+        };
+    }
+
+    // Chance of sampling each of the diffuse, specular, clearcoat and transmission lobes, in
+    // rough proportion to how much light each reflects. Only specular reflection and
+    // transmission happen on the inside of a surface.
+    fn lobe_probabilities(p: &Parameters, cos_theta: f64, front_face: bool) -> [f64; 4] {
+        let dielectric = 1.0 - p.metallic;
+        let outside = if front_face { 1.0 } else { 0.0 };
+        let white = Colour::new(1.0, 1.0, 1.0);
+        let specular = p
+            .specular_f0
+            .interpolate(white, schlick_weight(cos_theta))
+            .luminance()
+            .max(0.01);
+        let weights = [
+            dielectric * (1.0 - p.transmission) * outside,
+            specular,
+            0.25 * p.clearcoat * outside,
+            dielectric * p.transmission * (1.0 - specular),
+        ];
+        let total: f64 = weights.iter().sum();
+        return weights.map(|w| w / total);
+    }
+
+    fn eta(&self, record: &HitRecord) -> f64 {
+        if record.front_face {
+            return self.ior;
+        }
+        return 1.0 / self.ior;
+    }
+
+    // Returns the BSDF times the cosine, and the pdf of sampling the direction
+    fn evaluate(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> (Colour, f64) {
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let frame = Onb::from_w(record.normal);
+        let wo = frame.to_local(-ray.direction.unit());
+        let wi = frame.to_local(direction.unit());
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return (black, 0.0);
+        }
+        let p = self.parameters(record);
+        let probabilities = Principled::lobe_probabilities(&p, wo.z(), record.front_face);
+        let dielectric = 1.0 - p.metallic;
+
+        if wi.z() < 0.0 {
+            if probabilities[3] == 0.0 {
+                return (black, 0.0);
+            }
+            // Rough dielectric transmission, tinted by the base colour
+            let eta = self.eta(record);
+            let mut m = -(wo + wi * eta).unit();
+            if m.z() < 0.0 {
+                m = -m;
+            }
+            let (cos_o, cos_i) = (wo.dot(m), wi.dot(m));
+            if cos_o <= 0.0 || cos_i >= 0.0 {
+                return (black, 0.0);
+            }
+            let jacobian = eta * eta * cos_i.abs() / (cos_o + eta * cos_i).powi(2);
+            let f = (1.0 - fresnel_dielectric(cos_o, eta))
+                * p.specular.d(m)
+                * p.specular.g(wo, wi)
+                * cos_o
+                * jacobian
+                / wo.z();
+            let value = p.base_colour * (f * dielectric * p.transmission);
+            let pdf = probabilities[3] * p.specular.pdf_visible(wo, m) * jacobian;
+            return (value, pdf);
+        }
+
+        let h = (wo + wi).unit();
+        let cos_d = wi.dot(h);
+        let mut value = black;
+        let mut pdf = 0.0;
+
+        if record.front_face {
+            // Burley's diffuse, with retro-reflection at grazing angles, plus sheen
+            let weight = dielectric * (1.0 - p.transmission);
+            let f_d90 = 0.5 + 2.0 * p.roughness * cos_d * cos_d;
+            let retro = (1.0 + (f_d90 - 1.0) * schlick_weight(wi.z()))
+                * (1.0 + (f_d90 - 1.0) * schlick_weight(wo.z()));
+            value += p.base_colour * (weight * retro * wi.z() / PI);
+            value += p.sheen * (weight * schlick_weight(cos_d) * wi.z());
+            pdf += probabilities[0] * wi.z() / PI;
+
+            // A second, colourless specular lobe with a fixed index of refraction of 1.5
+            if p.clearcoat > 0.0 {
+                let d = gtr1(h.z(), p.clearcoat_alpha);
+                let g = Ggx::new(0.5, 0.5).g(wo, wi);
+                let f = 0.04 + 0.96 * schlick_weight(cos_d);
+                let coat = 0.25 * p.clearcoat * f * d * g / (4.0 * wo.z());
+                value += Colour::new(coat, coat, coat);
+                pdf += probabilities[2] * d * h.z() / (4.0 * wo.dot(h));
+            }
+        }
+
+        // Schlick Fresnel between the tinted dielectric and metallic reflectances
+        let white = Colour::new(1.0, 1.0, 1.0);
+        let f = p.specular_f0.interpolate(white, schlick_weight(cos_d));
+        value += f * (p.specular.d(h) * p.specular.g(wo, wi) / (4.0 * wo.z()));
+        pdf += probabilities[1] * p.specular.pdf_visible(wo, h) / (4.0 * wo.dot(h));
+        return (value, pdf);
+    }
+}
+
+fn schlick_weight(cos_theta: f64) -> f64 {
+    return (1.0 - cos_theta).clamp(0.0, 1.0).powi(5);
+}
+
+// Berry's distribution, with its long tail, for the clearcoat
+fn gtr1(cos_theta: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    let t = 1.0 + (a2 - 1.0) * cos_theta * cos_theta;
+    return (a2 - 1.0) / (PI * a2.ln() * t);
+}
+
+fn sample_gtr1(alpha: f64, u1: f64, u2: f64) -> Vector {
+    let a2 = alpha * alpha;
+    let cos_theta = ((1.0 - a2.powf(1.0 - u1)) / (1.0 - a2)).max(0.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    return Vector::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+}
+
+impl Material for Principled {
+    fn name(&self) -> &'static str {
+        return "principled";
+    }
+
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
+        let frame = Onb::from_w(record.normal);
+        let wo = frame.to_local(-ray.direction.unit());
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let p = self.parameters(record);
+        let probabilities = Principled::lobe_probabilities(&p, wo.z(), record.front_face);
+        let mut rng = rand::thread_rng();
+        let (u1, u2): (f64, f64) = (rng.gen(), rng.gen());
+
+        // Picks one lobe to sample, then weights by the pdf of all of them together
+        let mut choice = rng.gen::<f64>();
+        let mut lobe = 0;
+        while lobe < 3 && choice >= probabilities[lobe] {
+            choice -= probabilities[lobe];
+            lobe += 1;
+        }
+        let wi = match lobe {
+            0 => {
+                let r = u1.sqrt();
+                let phi = 2.0 * PI * u2;
+                Vector::new(r * phi.cos(), r * phi.sin(), (1.0 - u1).max(0.0).sqrt())
+            }
+            1 => reflect(wo, p.specular.sample_visible(wo, u1, u2)),
+            2 => reflect(wo, sample_gtr1(p.clearcoat_alpha, u1, u2)),
+            _ => {
+                // Past the critical angle the microfacet reflects everything instead
+                let m = p.specular.sample_visible(wo, u1, u2);
+                refract(wo, m, self.eta(record)).unwrap_or_else(|| reflect(wo, m))
+            }
+        };
+        // A reflection off a microfacet can still head below the surface
+        if lobe < 3 && wi.z() <= 0.0 {
+            return None;
+        }
+
+        let direction = frame.to_world(wi);
+        let (value, pdf) = self.evaluate(ray, record, direction);
+        if pdf <= 0.0 {
+            return None;
+        }
+        return Some((Ray::new(record.p, direction), value * (1.0 / pdf)));
+    }
+
+    fn eval(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> Colour {
+        return self.evaluate(ray, record, direction).0;
+    }
+
+    fn pdf(&self, ray: &Ray, record: &HitRecord, direction: Vector) -> f64 {
+        return self.evaluate(ray, record, direction).1;
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Colour {
+        return self.emission.value(record.u, record.v, record.p);
+    }
+}
+
+#[test]
+fn test_principled() {
+    use crate::point::Point;
+
+    // A white, non-absorbing surface shouldn't reflect more light than it receives
+    let material = Arc::new(Principled {
+        sheen: SolidColour::scalar(1.0),
+        clearcoat: SolidColour::scalar(1.0),
+        ..Principled::new(SolidColour::new(Colour::new(0.8, 0.8, 0.8)))
+    });
+    let normal = Vector::new(0.0, 1.0, 0.0);
+    let direction = Vector::new(0.3, -1.0, 0.0);
+    let ray = Ray::new(Point::new(0.0, 0.0, 0.0) - direction, direction);
+    let record = HitRecord::new_from_ray(
+        Point::new(0.0, 0.0, 0.0),
+        normal,
+        1.0,
+        &ray,
+        material.clone(),
+    );
+
+    let n = 4000;
+    let mut albedo = 0.0;
+    for _ in 0..n {
+        if let Some((scattered, weight)) = material.scatter(&ray, &record) {
+            let (value, pdf) = material.evaluate(&ray, &record, scattered.direction);
+            assert!((value.g / pdf - weight.g).abs() < 1e-6 * weight.g.max(1.0));
+            albedo += weight.g / n as f64;
+        }
+    }
+    assert!(albedo > 0.5 && albedo < 1.1);
+
+    // Fully transmissive materials send most light through
+    let glass = Arc::new(Principled {
+        transmission: SolidColour::scalar(1.0),
+        roughness: SolidColour::scalar(0.1),
+        ..Principled::new(SolidColour::new(Colour::new(1.0, 1.0, 1.0)))
+    });
+    let mut through = 0;
+    for _ in 0..100 {
+        if let Some((scattered, _)) = glass.scatter(&ray, &record) {
+            if scattered.direction.y() < 0.0 {
+                through += 1;
+            }
+        }
+    }
+    assert!(through > 80);
+}
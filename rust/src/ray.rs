@@ -1,14 +1,21 @@
 use crate::point::Point;
 use crate::vector::Vector;
 
+#[derive(Clone, Copy)]
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
+    // In nanometres, once the path is following a single wavelength of light
+    pub wavelength: Option<f64>,
 }
 
 impl Ray {
     pub fn new(origin: Point, direction: Vector) -> Ray {
-        return Ray { origin, direction };
+        return Ray {
+            origin,
+            direction,
+            wavelength: None,
+        };
     }
 
     pub fn at(&self, t: f64) -> Point {
@@ -0,0 +1,112 @@
+// Piecewise constant distribution over [0, 1), for importance sampling tabulated functions
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    pub integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Distribution1D {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f64;
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            // Falls back to uniform sampling when the function is zero everywhere
+            *c = if integral > 0.0 {
+                *c / integral
+            } else {
+                i as f64 / n as f64
+            };
+        }
+        return Distribution1D {
+            func,
+            cdf,
+            integral,
+        };
+    }
+
+    pub fn count(&self) -> usize {
+        return self.func.len();
+    }
+
+    // Returns the sampled position in [0, 1), its pdf and the index of the bucket it fell in
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let idx = self.cdf.partition_point(|&c| c <= u).clamp(1, self.count()) - 1;
+        let width = self.cdf[idx + 1] - self.cdf[idx];
+        let du = if width > 0.0 {
+            (u - self.cdf[idx]) / width
+        } else {
+            0.0
+        };
+        return (
+            (idx as f64 + du) / self.count() as f64,
+            self.pdf_at(idx),
+            idx,
+        );
+    }
+
+    pub fn pdf_at(&self, idx: usize) -> f64 {
+        if self.integral > 0.0 {
+            return self.func[idx].abs() / self.integral;
+        }
+        return 1.0;
+    }
+}
+
+// Piecewise constant distribution over [0, 1)^2, sampling a row and then a column within it
+pub struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // Takes the function's values row by row
+    pub fn new(func: &[f64], width: usize, height: usize) -> Distribution2D {
+        let rows: Vec<Distribution1D> = func
+            .chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(rows.iter().map(|row| row.integral).collect());
+        return Distribution2D { rows, marginal };
+    }
+
+    // Returns the sampled (u, v), where v selects the row, and its pdf
+    pub fn sample(&self, u1: f64, u2: f64) -> (f64, f64, f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u2);
+        let (u, pdf_u, _) = self.rows[row].sample(u1);
+        return (u, v, pdf_u * pdf_v);
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.marginal.count() as f64) as usize).min(self.marginal.count() - 1);
+        let columns = self.rows[row].count();
+        let column = ((u * columns as f64) as usize).min(columns - 1);
+        return self.marginal.pdf_at(row) * self.rows[row].pdf_at(column);
+    }
+}
+
+#[test]
+fn test_distribution() {
+    let dist = Distribution1D::new(vec![1.0, 0.0, 3.0]);
+    assert_eq!(dist.integral, 4.0 / 3.0);
+
+    let (x, pdf, idx) = dist.sample(0.1);
+    assert_eq!(idx, 0);
+    assert!((x - 0.4 / 3.0).abs() < 1e-12);
+    assert_eq!(pdf, 0.75);
+
+    let (x, pdf, idx) = dist.sample(0.625);
+    assert_eq!(idx, 2);
+    assert!((x - 0.5 / 3.0 - 2.0 / 3.0).abs() < 1e-12);
+    assert_eq!(pdf, 2.25);
+
+    let dist = Distribution2D::new(&[1.0, 1.0, 0.0, 2.0], 2, 2);
+    let (u, v, pdf) = dist.sample(0.5, 0.9);
+    assert_eq!((u, v), (0.75, 0.9));
+    assert_eq!(pdf, dist.pdf(u, v));
+    assert_eq!(pdf, 2.0);
+}
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use crate::background::Background;
+use crate::colour_space::{ColourSpace, Matrix};
+use crate::hittable::Environment;
+use crate::light::Light;
+
+pub struct Scene {
+    pub world: Environment,
+    pub background: Arc<dyn Background>,
+    pub lights: Vec<Light>,
+    // Colour space the scene's colours are given in, and rendered in
+    pub space: ColourSpace,
+    // Takes the scene's colours to sRGB's primaries, where spectra are fitted
+    pub to_srgb: Matrix,
+}
+
+impl Scene {
+    pub fn new(
+        world: Environment,
+        background: Arc<dyn Background>,
+        lights: Vec<Light>,
+        space: ColourSpace,
+    ) -> Scene {
+        return Scene {
+            world,
+            background,
+            lights,
+            space,
+            to_srgb: space.matrix_to(ColourSpace::LinearSrgb),
+        };
+    }
+}
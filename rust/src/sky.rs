@@ -0,0 +1,362 @@
+use rand::Rng;
+use std::f64::consts::PI;
+
+use crate::background::{Background, EnvironmentMap};
+use crate::colour::Colour;
+use crate::colour_space::{apply, ColourSpace, Matrix};
+use crate::image::Image;
+use crate::utils::{deg_to_rad, rad_to_deg};
+use crate::vector::Vector;
+
+// Angular radius of the sun's disk as seen from the earth
+const SUN_RADIUS: f64 = 0.004654;
+// Luminance of the sun's disk above the atmosphere, in kcd/m^2 like the sky model
+const SUN_LUMINANCE: f64 = 1.6e6;
+// Resolution of the table used to importance sample the sky
+const SKY_TABLE_WIDTH: u32 = 128;
+const SKY_TABLE_HEIGHT: u32 = 64;
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum SunPosition {
+    // Degrees above the horizon, and clockwise from -z (north) towards +x (east)
+    Angles {
+        elevation: f64,
+        azimuth: f64,
+    },
+    // Degrees north and east, with the time of day in hours UTC
+    Location {
+        latitude: f64,
+        longitude: f64,
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: f64,
+    },
+}
+
+impl SunPosition {
+    // Returns the sun's elevation and azimuth in degrees
+    pub fn angles(&self) -> (f64, f64) {
+        return match *self {
+            SunPosition::Angles { elevation, azimuth } => (elevation, azimuth),
+            SunPosition::Location {
+                latitude,
+                longitude,
+                year,
+                month,
+                day,
+                hour,
+            } => solar_position(latitude, longitude, day_of_year(year, month, day), hour),
+        };
+    }
+
+    pub fn direction(&self) -> Vector {
+        let (elevation, azimuth) = self.angles();
+        return direction(deg_to_rad(elevation), deg_to_rad(azimuth));
+    }
+}
+
+fn direction(elevation: f64, azimuth: f64) -> Vector {
+    return Vector::new(
+        elevation.cos() * azimuth.sin(),
+        elevation.sin(),
+        -elevation.cos() * azimuth.cos(),
+    );
+}
+
+fn day_of_year(year: i32, month: u32, day: u32) -> u32 {
+    const DAYS_BEFORE: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let month = month.clamp(1, 12);
+    return DAYS_BEFORE[(month - 1) as usize] + day + if leap && month > 2 { 1 } else { 0 };
+}
+
+// NOAA's low precision solar position, good to within a few tenths of a degree
+fn solar_position(latitude: f64, longitude: f64, day: u32, hour: f64) -> (f64, f64) {
+    let g = 2.0 * PI / 365.0 * (day as f64 - 1.0 + (hour - 12.0) / 24.0);
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * g.cos()
+            - 0.032077 * g.sin()
+            - 0.014615 * (2.0 * g).cos()
+            - 0.040849 * (2.0 * g).sin());
+    let declination = 0.006918 - 0.399912 * g.cos() + 0.070257 * g.sin()
+        - 0.006758 * (2.0 * g).cos()
+        + 0.000907 * (2.0 * g).sin()
+        - 0.002697 * (3.0 * g).cos()
+        + 0.00148 * (3.0 * g).sin();
+
+    // Minutes of true solar time give the hour angle, which is zero at solar noon
+    let solar_time = hour * 60.0 + equation_of_time + 4.0 * longitude;
+    let hour_angle = deg_to_rad(solar_time / 4.0 - 180.0);
+    let lat = deg_to_rad(latitude);
+
+    let elevation = (lat.sin() * declination.sin()
+        + lat.cos() * declination.cos() * hour_angle.cos())
+    .clamp(-1.0, 1.0)
+    .asin();
+    let azimuth = hour_angle
+        .sin()
+        .atan2(hour_angle.cos() * lat.sin() - declination.tan() * lat.cos())
+        + PI;
+    return (rad_to_deg(elevation), rad_to_deg(azimuth).rem_euclid(360.0));
+}
+
+// Preetham, Shirley and Smits' analytic daylight model, without the sun itself
+struct Preetham {
+    sun: Vector,
+    sun_theta: f64,
+    // Perez coefficients and zenith values for luminance and the two chromaticities
+    perez: [[f64; 5]; 3],
+    zenith: [f64; 3],
+    intensity: f64,
+}
+
+impl Preetham {
+    fn new(sun: Vector, turbidity: f64, intensity: f64) -> Preetham {
+        let t = turbidity;
+        // The model breaks down once the sun sets, so it's held just above the horizon
+        let sun_theta = sun.y().clamp(0.01, 1.0).acos();
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * sun_theta);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let (s, s2, s3) = (sun_theta, sun_theta * sun_theta, sun_theta.powi(3));
+        let x = t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+        let y = t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+
+        return Preetham {
+            sun,
+            sun_theta,
+            perez,
+            zenith: [luminance, x, y],
+            intensity,
+        };
+    }
+
+    fn perez(&self, channel: usize, cos_theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.perez[channel];
+        return (1.0 + a * (b / cos_theta).exp())
+            * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos());
+    }
+
+    // Below the horizon the sky is mirrored, standing in for distant ground
+    fn colour(&self, direction: Vector) -> Colour {
+        let mut d = direction.unit();
+        d[1] = d.y().abs();
+        let gamma = d.dot(self.sun).clamp(-1.0, 1.0).acos();
+        let mut xyy = [0.0; 3];
+        for (channel, value) in xyy.iter_mut().enumerate() {
+            *value = self.zenith[channel] * self.perez(channel, d.y().max(0.01), gamma)
+                / self.perez(channel, 1.0, self.sun_theta);
+        }
+        return xyy_to_rgb(xyy[0] * self.intensity, xyy[1], xyy[2]);
+    }
+}
+
+// A Preetham sky with the sun's disk added on top
+pub struct Sky {
+    sun: Vector,
+    sun_colour: Colour,
+    model: Preetham,
+    // The sky tabulated for importance sampling, with the sun's disk sampled separately
+    table: EnvironmentMap,
+    // From the model's sRGB to the working space
+    to_working: Matrix,
+}
+
+impl Sky {
+    // Turbidity runs from about 2 for a clear sky to 10 for haze. Intensity scales the
+    // model's kcd/m^2 into scene units.
+    pub fn new(sun: SunPosition, turbidity: f64, intensity: f64, working: ColourSpace) -> Sky {
+        let model = Preetham::new(sun.direction(), turbidity, intensity);
+        let mut pixels = Vec::with_capacity((SKY_TABLE_WIDTH * SKY_TABLE_HEIGHT) as usize);
+        for j in 0..SKY_TABLE_HEIGHT {
+            for i in 0..SKY_TABLE_WIDTH {
+                let theta = PI * (j as f64 + 0.5) / SKY_TABLE_HEIGHT as f64;
+                let phi = 2.0 * PI * ((i as f64 + 0.5) / SKY_TABLE_WIDTH as f64 - 0.5);
+                pixels.push(model.colour(direction(PI / 2.0 - theta, phi)));
+            }
+        }
+        let table = EnvironmentMap::new(
+            Image::new(SKY_TABLE_WIDTH, SKY_TABLE_HEIGHT, pixels),
+            0.0,
+            1.0,
+        );
+        return Sky {
+            sun: model.sun,
+            sun_colour: sun_colour(model.sun_theta, turbidity) * intensity,
+            model,
+            table,
+            to_working: ColourSpace::LinearSrgb.matrix_to(working),
+        };
+    }
+
+    fn in_sun(&self, direction: Vector) -> bool {
+        return direction.unit().dot(self.sun) >= SUN_RADIUS.cos() && self.sun.y() > 0.0;
+    }
+
+    // Probability of aiming a light sample at the sun rather than the sky
+    fn sun_probability(&self) -> f64 {
+        if self.sun.y() <= 0.0 {
+            return 0.0;
+        }
+        return 0.5;
+    }
+}
+
+// Attenuates the sun's light by Rayleigh and aerosol scattering along its path through the
+// atmosphere, evaluated at the red, green and blue primaries' wavelengths
+fn sun_colour(theta: f64, turbidity: f64) -> Colour {
+    let air_mass = 1.0 / (theta.cos() + 0.15 * (93.885 - rad_to_deg(theta)).max(0.01).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let transmittance = |lambda: f64| -> f64 {
+        let rayleigh = (-0.008735 * lambda.powf(-4.08) * air_mass).exp();
+        let aerosol = (-beta * lambda.powf(-1.3) * air_mass).exp();
+        return rayleigh * aerosol;
+    };
+    return Colour::new(
+        transmittance(0.61),
+        transmittance(0.55),
+        transmittance(0.465),
+    ) * SUN_LUMINANCE;
+}
+
+fn xyy_to_rgb(luminance: f64, x: f64, y: f64) -> Colour {
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    return Colour::new(
+        (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+        (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+        (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+    );
+}
+
+impl Background for Sky {
+    fn colour(&self, direction: Vector) -> Colour {
+        let sky = self.model.colour(direction);
+        if self.in_sun(direction) {
+            return apply(self.to_working, sky + self.sun_colour);
+        }
+        return apply(self.to_working, sky);
+    }
+
+    fn sample(&self) -> Option<(Vector, Colour, f64)> {
+        let mut rng = rand::thread_rng();
+        let direction = if rng.gen::<f64>() < self.sun_probability() {
+            // Uniform over the cone subtended by the sun's disk
+            let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - SUN_RADIUS.cos());
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let phi = 2.0 * PI * rng.gen::<f64>();
+            let helper = if self.sun.x().abs() > 0.9 {
+                Vector::new(0.0, 1.0, 0.0)
+            } else {
+                Vector::new(1.0, 0.0, 0.0)
+            };
+            let u = self.sun.cross(helper).unit();
+            let v = self.sun.cross(u);
+            (u * phi.cos() + v * phi.sin()) * sin_theta + self.sun * cos_theta
+        } else {
+            self.table.sample()?.0
+        };
+        let pdf = self.pdf(direction);
+        if pdf == 0.0 {
+            return None;
+        }
+        return Some((direction, self.colour(direction), pdf));
+    }
+
+    fn pdf(&self, direction: Vector) -> f64 {
+        let p = self.sun_probability();
+        let mut pdf = (1.0 - p) * self.table.pdf(direction);
+        if self.in_sun(direction) {
+            pdf += p / (2.0 * PI * (1.0 - SUN_RADIUS.cos()));
+        }
+        return pdf;
+    }
+}
+
+#[test]
+fn test_sky() {
+    // Near the equator at an equinox's solar noon the sun is almost overhead
+    let noon = SunPosition::Location {
+        latitude: 0.0,
+        longitude: 0.0,
+        year: 2024,
+        month: 3,
+        day: 20,
+        hour: 12.1,
+    };
+    let (elevation, _) = noon.angles();
+    assert!(elevation > 85.0);
+
+    // The sun rises in the east, which is +x
+    let morning = SunPosition::Location {
+        latitude: 51.5,
+        longitude: 0.0,
+        year: 2024,
+        month: 6,
+        day: 21,
+        hour: 7.0,
+    };
+    let (elevation, azimuth) = morning.angles();
+    assert!(elevation > 10.0 && elevation < 40.0);
+    assert!(azimuth > 60.0 && azimuth < 120.0);
+    assert!(morning.direction().x() > 0.5);
+
+    let sky = Sky::new(
+        SunPosition::Angles {
+            elevation: 30.0,
+            azimuth: 90.0,
+        },
+        3.0,
+        0.1,
+        ColourSpace::LinearSrgb,
+    );
+    // Brightest around the sun, and its disk far brighter still
+    let towards = sky.colour(direction(deg_to_rad(30.0), deg_to_rad(70.0)));
+    let away = sky.colour(direction(deg_to_rad(30.0), deg_to_rad(-90.0)));
+    assert!(towards.luminance() > away.luminance());
+    assert!(sky.colour(sky.sun).luminance() > 1000.0 * towards.luminance());
+    let above = sky.colour(Vector::new(0.0, 0.5, 1.0));
+    let below = sky.colour(Vector::new(0.0, -0.5, 1.0));
+    assert_eq!(above.luminance(), below.luminance());
+
+    let mut sun_samples = 0;
+    for _ in 0..200 {
+        let (direction, colour, pdf) = sky.sample().unwrap();
+        assert!((pdf - sky.pdf(direction)).abs() < 1e-6 * pdf);
+        if sky.in_sun(direction) {
+            assert!(colour.luminance() > 1000.0);
+            sun_samples += 1;
+        }
+    }
+    assert!(sun_samples > 50 && sun_samples < 150);
+}
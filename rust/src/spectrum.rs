@@ -0,0 +1,484 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::colour::Colour;
+
+// Visible range sampled by paths that follow a single wavelength, in nanometres
+pub const WAVELENGTH_MIN: f64 = 380.0;
+pub const WAVELENGTH_MAX: f64 = 780.0;
+// Sodium D line, where refractive indices are usually quoted
+pub const D_LINE: f64 = 589.3;
+
+// Steps used to integrate spectra into colours
+const INTEGRATION_STEPS: usize = 40;
+// Resolution of the table of fitted spectra along each axis
+const TABLE_RES: usize = 24;
+// Gauss-Newton iterations improving each colour's fit from the table
+const REFINEMENT_STEPS: usize = 1;
+const MAX_COEFFICIENT: f64 = 200.0;
+// Fitted spectra each thread keeps before starting afresh
+const CACHE_SIZE: usize = 4096;
+
+// Piecewise Gaussian with different widths either side of its peak
+fn lobe(x: f64, mu: f64, sigma_below: f64, sigma_above: f64) -> f64 {
+    let sigma = if x < mu { sigma_below } else { sigma_above };
+    return (-0.5 * ((x - mu) / sigma).powi(2)).exp();
+}
+
+// CIE 1931 colour matching functions, from the multi-lobe fit of Wyman, Sloan and Shirley,
+// "Simple Analytic Approximations to the CIE XYZ Color Matching Functions", 2013
+pub fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let x = 1.056 * lobe(wavelength, 599.8, 37.9, 31.0)
+        + 0.362 * lobe(wavelength, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(wavelength, 501.1, 20.4, 26.2);
+    let y =
+        0.821 * lobe(wavelength, 568.8, 46.9, 40.5) + 0.286 * lobe(wavelength, 530.9, 16.3, 31.1);
+    let z =
+        1.217 * lobe(wavelength, 437.0, 11.8, 36.0) + 0.681 * lobe(wavelength, 459.0, 26.0, 13.8);
+    return (x, y, z);
+}
+
+// Linear sRGB primaries with a D65 white point
+pub fn xyz_to_rgb(x: f64, y: f64, z: f64) -> Colour {
+    return Colour::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    );
+}
+
+fn unnormalised_rgb(wavelength: f64) -> Colour {
+    let (x, y, z) = cie_xyz(wavelength);
+    return xyz_to_rgb(x, y, z);
+}
+
+// Average colour of the wavelengths across the range, which an equal energy spectrum maps to
+fn white() -> Colour {
+    static WHITE: OnceLock<Colour> = OnceLock::new();
+    return *WHITE.get_or_init(|| {
+        let step = (WAVELENGTH_MAX - WAVELENGTH_MIN) / 1000.0;
+        let mut total = Colour::new(0.0, 0.0, 0.0);
+        for i in 0..1000 {
+            total += unnormalised_rgb(WAVELENGTH_MIN + (i as f64 + 0.5) * step) * (1.0 / 1000.0);
+        }
+        return total;
+    });
+}
+
+// Colour carried by light of a single wavelength, scaled so that the average over the range
+// is white. A path that picks its wavelength uniformly can weight its result by this, and
+// white light still comes out white on average. Some channels go negative outside sRGB.
+pub fn wavelength_rgb(wavelength: f64) -> Colour {
+    let c = unnormalised_rgb(wavelength);
+    let w = white();
+    return Colour::new(c.r / w.r, c.g / w.g, c.b / w.b);
+}
+
+pub fn sample_wavelength(u: f64) -> f64 {
+    return WAVELENGTH_MIN + u * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+}
+
+// Wavelengths integrated over, as fractions of the way across the range, and the share of
+// colour each contributes
+fn integration_steps() -> &'static [(f64, Colour); INTEGRATION_STEPS] {
+    static STEPS: OnceLock<[(f64, Colour); INTEGRATION_STEPS]> = OnceLock::new();
+    return STEPS.get_or_init(|| {
+        return std::array::from_fn(|i| {
+            let t = (i as f64 + 0.5) / INTEGRATION_STEPS as f64;
+            let wavelength = WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN);
+            (
+                t,
+                wavelength_rgb(wavelength) * (1.0 / INTEGRATION_STEPS as f64),
+            )
+        });
+    });
+}
+
+// Colour of a reflectance spectrum lit by white light
+pub fn integrate(spectrum: impl Fn(f64) -> f64) -> Colour {
+    let mut total = Colour::new(0.0, 0.0, 0.0);
+    for (t, weight) in integration_steps() {
+        total += *weight * spectrum(WAVELENGTH_MIN + t * (WAVELENGTH_MAX - WAVELENGTH_MIN));
+    }
+    return total;
+}
+
+// Reads a quantity given per colour channel at a wavelength, taking the channels to stand
+// for 650, 550 and 450nm and interpolating between them
+pub fn channel_at(colour: Colour, wavelength: f64) -> f64 {
+    if wavelength >= 650.0 {
+        return colour.r;
+    } else if wavelength >= 550.0 {
+        let t = (wavelength - 550.0) / 100.0;
+        return colour.g * (1.0 - t) + colour.r * t;
+    } else if wavelength >= 450.0 {
+        let t = (wavelength - 450.0) / 100.0;
+        return colour.b * (1.0 - t) + colour.g * t;
+    }
+    return colour.b;
+}
+
+// Picks wavelengths for a spectral path, spread evenly across the range from a hero
+// wavelength, as in Wilkie et al., "Hero Wavelength Spectral Sampling", 2014. The first is the
+// hero, which wavelength dependent scattering follows alone.
+pub fn sample_wavelengths(u: f64) -> [f64; 3] {
+    let range = WAVELENGTH_MAX - WAVELENGTH_MIN;
+    let hero = sample_wavelength(u);
+    return [0.0, 1.0, 2.0].map(|i| {
+        let wavelength = hero + i * range / 3.0;
+        if wavelength > WAVELENGTH_MAX {
+            wavelength - range
+        } else {
+            wavelength
+        }
+    });
+}
+
+// Converts radiance at uniformly sampled wavelengths into colour, by accumulating their
+// average into CIE XYZ and taking that to linear sRGB, balanced so a flat spectrum is white
+pub fn samples_to_rgb(wavelengths: [f64; 3], values: [f64; 3]) -> Colour {
+    let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+    for (wavelength, value) in wavelengths.iter().zip(values) {
+        let (cx, cy, cz) = cie_xyz(*wavelength);
+        x += cx * value / 3.0;
+        y += cy * value / 3.0;
+        z += cz * value / 3.0;
+    }
+    let c = xyz_to_rgb(x, y, z);
+    let w = white();
+    return Colour::new(c.r / w.r, c.g / w.g, c.b / w.b);
+}
+
+fn sigmoid(x: f64) -> f64 {
+    if x.is_infinite() {
+        return if x > 0.0 { 1.0 } else { 0.0 };
+    }
+    return 0.5 + x / (2.0 * (1.0 + x * x).sqrt());
+}
+
+// A smooth spectrum standing in for an RGB colour, from Jakob and Hanika, "A Low-Dimensional
+// Function Space for Efficient Spectral Upsampling", 2019. A quadratic in the wavelength
+// squashed by a sigmoid stays between zero and one, and is then scaled for colours above one.
+#[derive(Clone, Copy)]
+pub struct SigmoidSpectrum {
+    coefficients: [f64; 3],
+    scale: f64,
+}
+
+impl SigmoidSpectrum {
+    pub fn from_rgb(colour: Colour) -> SigmoidSpectrum {
+        let rgb = [colour.r.max(0.0), colour.g.max(0.0), colour.b.max(0.0)];
+        let m = rgb[0].max(rgb[1]).max(rgb[2]);
+        if m <= 0.0 {
+            return SigmoidSpectrum {
+                coefficients: [0.0, 0.0, 0.0],
+                scale: 0.0,
+            };
+        }
+        // Reflectances fit directly, while brighter colours fit their shape
+        let scale = m.max(1.0);
+        let target = rgb.map(|c| c / scale);
+        // The table is too coarse for saturated colours alone, so the fit is refined from it
+        let coefficients = gauss_newton(table().lookup(target), target, REFINEMENT_STEPS);
+        return SigmoidSpectrum {
+            coefficients,
+            scale,
+        };
+    }
+
+    // Fits the colour, or reuses the fit from the last time this thread met it. Constant
+    // colours, such as those of plain materials and media, are so fitted just once, while
+    // colours that change from hit to hit, such as texture lookups, are fitted each time.
+    pub fn cached(colour: Colour) -> SigmoidSpectrum {
+        thread_local! {
+            static FITTED: RefCell<HashMap<[u64; 3], SigmoidSpectrum>> =
+                RefCell::new(HashMap::new());
+        }
+        let key = [colour.r.to_bits(), colour.g.to_bits(), colour.b.to_bits()];
+        return FITTED.with(|fitted| {
+            let mut fitted = fitted.borrow_mut();
+            if let Some(spectrum) = fitted.get(&key) {
+                return *spectrum;
+            }
+            // Varying colours would otherwise fill it without end
+            if fitted.len() >= CACHE_SIZE {
+                fitted.clear();
+            }
+            let spectrum = SigmoidSpectrum::from_rgb(colour);
+            fitted.insert(key, spectrum);
+            return spectrum;
+        });
+    }
+
+    pub fn evaluate(&self, wavelength: f64) -> f64 {
+        let t = (wavelength - WAVELENGTH_MIN) / (WAVELENGTH_MAX - WAVELENGTH_MIN);
+        let [c0, c1, c2] = self.coefficients;
+        return self.scale * sigmoid((c0 * t + c1) * t + c2);
+    }
+}
+
+// Coefficients fitted ahead of time over a grid of colours. For each choice of largest
+// channel, the grid runs over that channel's value, on a scale denser near black and white,
+// and the other two channels as fractions of it.
+struct SigmoidTable {
+    scales: Vec<f64>,
+    coefficients: Vec<[f64; 3]>,
+}
+
+fn table() -> &'static SigmoidTable {
+    static TABLE: OnceLock<SigmoidTable> = OnceLock::new();
+    return TABLE.get_or_init(SigmoidTable::fit);
+}
+
+impl SigmoidTable {
+    fn index(largest: usize, k: usize, j: usize, i: usize) -> usize {
+        return ((largest * TABLE_RES + k) * TABLE_RES + j) * TABLE_RES + i;
+    }
+
+    fn fit() -> SigmoidTable {
+        let smoothstep = |x: f64| x * x * (3.0 - 2.0 * x);
+        let scales: Vec<f64> = (0..TABLE_RES)
+            .map(|k| smoothstep(smoothstep(k as f64 / (TABLE_RES - 1) as f64)))
+            .collect();
+        let mut coefficients = vec![[0.0; 3]; 3 * TABLE_RES.pow(3)];
+        let target = |largest: usize, k: usize, j: usize, i: usize| -> [f64; 3] {
+            let z = scales[k];
+            let mut rgb = [0.0; 3];
+            rgb[largest] = z;
+            rgb[(largest + 1) % 3] = i as f64 / (TABLE_RES - 1) as f64 * z;
+            rgb[(largest + 2) % 3] = j as f64 / (TABLE_RES - 1) as f64 * z;
+            return rgb;
+        };
+        // Fits outwards from a mid grey, starting each fit from its less extreme neighbour
+        let start = TABLE_RES / 5;
+        for largest in 0..3 {
+            for j in 0..TABLE_RES {
+                for i in 0..TABLE_RES {
+                    let mut c = [0.0; 3];
+                    for k in start..TABLE_RES {
+                        c = gauss_newton(c, target(largest, k, j, i), 30);
+                        coefficients[Self::index(largest, k, j, i)] = c;
+                    }
+                    c = coefficients[Self::index(largest, start, j, i)];
+                    for k in (0..start).rev() {
+                        c = gauss_newton(c, target(largest, k, j, i), 30);
+                        coefficients[Self::index(largest, k, j, i)] = c;
+                    }
+                }
+            }
+        }
+        return SigmoidTable {
+            scales,
+            coefficients,
+        };
+    }
+
+    // Interpolates the coefficients for a colour with channels between zero and one
+    fn lookup(&self, rgb: [f64; 3]) -> [f64; 3] {
+        let rgb = rgb.map(|c| c.clamp(0.0, 1.0));
+        let largest = if rgb[0] >= rgb[1] && rgb[0] >= rgb[2] {
+            0
+        } else if rgb[1] >= rgb[2] {
+            1
+        } else {
+            2
+        };
+        let z = rgb[largest];
+        let x = rgb[(largest + 1) % 3] / z * (TABLE_RES - 1) as f64;
+        let y = rgb[(largest + 2) % 3] / z * (TABLE_RES - 1) as f64;
+        let k = self
+            .scales
+            .partition_point(|s| *s <= z)
+            .clamp(1, TABLE_RES - 1)
+            - 1;
+        let (i, j) = (
+            (x as usize).min(TABLE_RES - 2),
+            (y as usize).min(TABLE_RES - 2),
+        );
+        let tz = ((z - self.scales[k]) / (self.scales[k + 1] - self.scales[k])).clamp(0.0, 1.0);
+        let (tx, ty) = (x - i as f64, y - j as f64);
+
+        let mut out = [0.0; 3];
+        for (dk, wk) in [(0, 1.0 - tz), (1, tz)] {
+            for (dj, wj) in [(0, 1.0 - ty), (1, ty)] {
+                for (di, wi) in [(0, 1.0 - tx), (1, tx)] {
+                    let c = self.coefficients[Self::index(largest, k + dk, j + dj, i + di)];
+                    for n in 0..3 {
+                        out[n] += c[n] * wk * wj * wi;
+                    }
+                }
+            }
+        }
+        return out;
+    }
+}
+
+// Difference between the colour of the coefficients' spectrum and the target, and its
+// derivatives with respect to the coefficients
+fn fit_error(c: [f64; 3], target: [f64; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut residual = [-target[0], -target[1], -target[2]];
+    let mut jacobian = [[0.0; 3]; 3];
+    for &(t, weight) in integration_steps() {
+        let x = (c[0] * t + c[1]) * t + c[2];
+        let slope = 0.5 / (1.0 + x * x).powf(1.5);
+        for (row, w) in [weight.r, weight.g, weight.b].into_iter().enumerate() {
+            residual[row] += w * sigmoid(x);
+            jacobian[row][0] += w * slope * t * t;
+            jacobian[row][1] += w * slope * t;
+            jacobian[row][2] += w * slope;
+        }
+    }
+    return (residual, jacobian);
+}
+
+// Refines sigmoid coefficients towards a spectrum with the target colour under white light,
+// shortening steps that would make the fit worse
+fn gauss_newton(mut c: [f64; 3], target: [f64; 3], iterations: usize) -> [f64; 3] {
+    let norm = |r: [f64; 3]| r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
+    let (mut residual, mut jacobian) = fit_error(c, target);
+    for _ in 0..iterations {
+        if norm(residual) < 1e-12 {
+            break;
+        }
+        let step = match solve(jacobian, residual) {
+            Some(step) => step,
+            None => break,
+        };
+        let mut length = 1.0;
+        loop {
+            // Saturated colours drive the coefficients off towards infinity
+            let next = [0, 1, 2]
+                .map(|n| (c[n] - step[n] * length).clamp(-MAX_COEFFICIENT, MAX_COEFFICIENT));
+            let (next_residual, next_jacobian) = fit_error(next, target);
+            if norm(next_residual) < norm(residual) {
+                (c, residual, jacobian) = (next, next_residual, next_jacobian);
+                break;
+            }
+            length *= 0.5;
+            if length < 1e-3 {
+                return c;
+            }
+        }
+    }
+    return c;
+}
+
+// Solves a 3x3 linear system by Cramer's rule
+fn solve(a: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| -> f64 {
+        return m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    };
+    let d = det(a);
+    if d.abs() < 1e-300 {
+        return None;
+    }
+    let mut x = [0.0; 3];
+    for (col, x) in x.iter_mut().enumerate() {
+        let mut m = a;
+        for row in 0..3 {
+            m[row][col] = b[row];
+        }
+        *x = det(m) / d;
+    }
+    return Some(x);
+}
+
+// How a material's refractive index varies with wavelength
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Dispersion {
+    // n = a + b / λ², with λ in micrometres
+    Cauchy { a: f64, b: f64 },
+    // n² = 1 + Σ bᵢλ² / (λ² - cᵢ), with λ in micrometres
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    // Schott N-BK7, a common crown glass
+    #[allow(dead_code)]
+    pub const BK7: Dispersion = Dispersion::Sellmeier {
+        b: [1.03961212, 0.231792344, 1.01046945],
+        c: [0.00600069867, 0.0200179144, 103.560653],
+    };
+    // Schott N-SF11, a dense flint glass that splits light strongly
+    #[allow(dead_code)]
+    pub const DENSE_FLINT: Dispersion = Dispersion::Sellmeier {
+        b: [1.73759695, 0.313747346, 1.89878101],
+        c: [0.013188707, 0.0623068142, 155.23629],
+    };
+
+    // Refractive index at a wavelength in nanometres
+    pub fn ior(&self, wavelength: f64) -> f64 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        return match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: f64 = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        };
+    }
+}
+
+#[test]
+fn test_spectrum() {
+    // Integrating a flat spectrum gives back its value as a grey
+    let grey = integrate(|_| 0.5);
+    assert!(
+        (grey.r - 0.5).abs() < 0.01 && (grey.g - 0.5).abs() < 0.01 && (grey.b - 0.5).abs() < 0.01
+    );
+    // Long wavelengths are red and short ones blue
+    let red = wavelength_rgb(650.0);
+    assert!(red.r > red.g && red.r > red.b);
+    let blue = wavelength_rgb(450.0);
+    assert!(blue.b > blue.r && blue.b > blue.g);
+
+    // Glass bends blue light more than red, and BK7 is 1.5168 at the d line
+    assert!((Dispersion::BK7.ior(587.56) - 1.5168).abs() < 1e-4);
+    assert!(Dispersion::DENSE_FLINT.ior(450.0) > Dispersion::DENSE_FLINT.ior(650.0));
+    let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.01 };
+    assert!((cauchy.ior(500.0) - 1.54).abs() < 1e-12);
+
+    // Upsampled colours come back to themselves, and brighter ones scale their shape
+    for rgb in [
+        Colour::new(0.8, 0.1, 0.1),
+        Colour::new(0.2, 0.5, 0.3),
+        Colour::new(0.1, 0.2, 0.5),
+        Colour::new(0.5, 0.5, 0.5),
+        Colour::new(0.9, 0.7, 0.02),
+    ] {
+        let spectrum = SigmoidSpectrum::from_rgb(rgb);
+        let c = integrate(|wavelength| spectrum.evaluate(wavelength));
+        assert!(
+            (c.r - rgb.r).abs() < 0.02 && (c.g - rgb.g).abs() < 0.02 && (c.b - rgb.b).abs() < 0.02
+        );
+    }
+    let cached = SigmoidSpectrum::cached(Colour::new(0.1, 0.2, 0.5));
+    assert_eq!(
+        cached.evaluate(500.0),
+        SigmoidSpectrum::from_rgb(Colour::new(0.1, 0.2, 0.5)).evaluate(500.0)
+    );
+    let bright = SigmoidSpectrum::from_rgb(Colour::new(4.0, 2.0, 1.0));
+    let dim = SigmoidSpectrum::from_rgb(Colour::new(1.0, 0.5, 0.25));
+    assert!((bright.evaluate(500.0) - 4.0 * dim.evaluate(500.0)).abs() < 1e-9);
+    assert_eq!(
+        SigmoidSpectrum::from_rgb(Colour::new(0.0, 0.0, 0.0)).evaluate(500.0),
+        0.0
+    );
+
+    // Hero wavelengths average a flat spectrum out to white
+    let n = 3000;
+    let mut total = Colour::new(0.0, 0.0, 0.0);
+    for i in 0..n {
+        let wavelengths = sample_wavelengths((i as f64 + 0.5) / n as f64);
+        total += samples_to_rgb(wavelengths, [1.0, 1.0, 1.0]) * (1.0 / n as f64);
+    }
+    assert!(
+        (total.r - 1.0).abs() < 1e-3
+            && (total.g - 1.0).abs() < 1e-3
+            && (total.b - 1.0).abs() < 1e-3
+    );
+}
@@ -1,9 +1,11 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
-use crate::hittable::{Hit, HitRecord};
+use crate::hittable::{Hit, HitRecord, Interval};
 use crate::material::Material;
 use crate::point::Point;
 use crate::ray::Ray;
+use crate::vector::Vector;
 
 pub struct Sphere {
     pub centre: Point,
@@ -21,8 +23,9 @@ impl Sphere {
     }
 }
 
-impl Hit for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+impl Sphere {
+    // Where the ray's line meets the sphere, nearest first
+    fn roots(&self, ray: &Ray) -> Option<(f64, f64)> {
         let oc = ray.origin - self.centre;
         let a = ray.direction.dot(ray.direction);
         let half_b = oc.dot(ray.direction);
@@ -33,17 +36,52 @@ impl Hit for Sphere {
             return None;
         };
         let sqrtd = discriminant.powf(0.5);
-        let mut t = (-half_b - sqrtd) / a;
+        return Some(((-half_b - sqrtd) / a, (-half_b + sqrtd) / a));
+    }
+
+    fn record(&self, ray: &Ray, t: f64) -> HitRecord {
+        let p = ray.at(t);
+        let normal = (p - self.centre) / self.radius;
+        let mut rec = HitRecord::new_from_ray(p, normal, t, ray, self.material.clone());
+        // Longitude and latitude, with v running up from the bottom of the sphere
+        rec.u = ((-normal.z()).atan2(normal.x()) + PI) / (2.0 * PI);
+        rec.v = (-normal.y()).clamp(-1.0, 1.0).acos() / PI;
+        // Differentiates the mapping, leaving the record's arbitrary frame at the poles
+        let (x, y, z) = (
+            p.x() - self.centre.x(),
+            p.y() - self.centre.y(),
+            p.z() - self.centre.z(),
+        );
+        let sin_theta = (x * x + z * z).sqrt() / self.radius;
+        if sin_theta > 1e-9 {
+            let cot_theta = -y / self.radius / sin_theta;
+            rec.dpdu = Vector::new(z, 0.0, -x) * (2.0 * PI);
+            rec.dpdv = Vector::new(x * cot_theta, self.radius * sin_theta, z * cot_theta) * PI;
+        }
+        return rec;
+    }
+}
+
+impl Hit for Sphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (near, far) = self.roots(ray)?;
+        let mut t = near;
         if t < t_min || t > t_max {
-            t = (-half_b + sqrtd) / a;
+            t = far;
             if t < t_min || t > t_max {
                 return None;
             }
         }
+        return Some(self.record(ray, t));
+    }
 
-        let p = ray.at(t);
-        let normal = (p - self.centre) / self.radius;
-        let rec = HitRecord::new_from_ray(p, normal, t, ray, self.material.clone());
-        return Some(rec);
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        return match self.roots(ray) {
+            Some((near, far)) => vec![Interval {
+                enter: self.record(ray, near),
+                exit: self.record(ray, far),
+            }],
+            None => Vec::new(),
+        };
     }
 }
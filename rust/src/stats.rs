@@ -0,0 +1,183 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Counters are gathered per thread and merged into the global totals by `flush`,
+// so that the hot paths never contend on a shared lock
+#[derive(Clone, Default)]
+pub struct Stats {
+    pub camera_rays: u64,
+    pub rays: u64,
+    pub shadow_rays: u64,
+    pub intersection_tests: u64,
+    // The scene is a flat list with no BVH yet, so this stays zero. It's reported anyway, so
+    // the report's fields don't change once there is one.
+    pub bvh_nodes_visited: u64,
+    pub scatters: BTreeMap<&'static str, u64>,
+}
+
+impl Stats {
+    const fn new() -> Stats {
+        return Stats {
+            camera_rays: 0,
+            rays: 0,
+            shadow_rays: 0,
+            intersection_tests: 0,
+            bvh_nodes_visited: 0,
+            scatters: BTreeMap::new(),
+        };
+    }
+
+    fn merge(&mut self, other: &Stats) {
+        self.camera_rays += other.camera_rays;
+        self.rays += other.rays;
+        self.shadow_rays += other.shadow_rays;
+        self.intersection_tests += other.intersection_tests;
+        self.bvh_nodes_visited += other.bvh_nodes_visited;
+        for (name, count) in other.scatters.iter() {
+            *self.scatters.entry(name).or_insert(0) += count;
+        }
+    }
+}
+
+thread_local! {
+    static LOCAL: RefCell<Stats> = const { RefCell::new(Stats::new()) };
+}
+
+static GLOBAL: Mutex<Stats> = Mutex::new(Stats::new());
+
+pub fn camera_ray() {
+    LOCAL.with(|s| s.borrow_mut().camera_rays += 1);
+}
+
+pub fn ray() {
+    LOCAL.with(|s| s.borrow_mut().rays += 1);
+}
+
+// Shadow rays also count towards the total rays traced
+pub fn shadow_ray() {
+    LOCAL.with(|s| {
+        let mut s = s.borrow_mut();
+        s.rays += 1;
+        s.shadow_rays += 1;
+    });
+}
+
+pub fn intersection_tests(n: u64) {
+    LOCAL.with(|s| s.borrow_mut().intersection_tests += n);
+}
+
+pub fn scatter(material: &'static str) {
+    LOCAL.with(|s| *s.borrow_mut().scatters.entry(material).or_insert(0) += 1);
+}
+
+pub fn flush() {
+    let local = LOCAL.with(|s| s.replace(Stats::new()));
+    GLOBAL.lock().unwrap().merge(&local);
+}
+
+pub fn collect() -> Stats {
+    return GLOBAL.lock().unwrap().clone();
+}
+
+pub struct RenderReport {
+    pub wall_time: Duration,
+    pub stats: Stats,
+}
+
+impl RenderReport {
+    fn per_camera_ray(&self, count: u64) -> f64 {
+        return count as f64 / self.stats.camera_rays.max(1) as f64;
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        return self.stats.rays as f64 / self.wall_time.as_secs_f64().max(1e-9);
+    }
+
+    pub fn average_path_length(&self) -> f64 {
+        return self.per_camera_ray(self.stats.rays - self.stats.shadow_rays);
+    }
+
+    pub fn intersection_tests_per_ray(&self) -> f64 {
+        return self.stats.intersection_tests as f64 / self.stats.rays.max(1) as f64;
+    }
+
+    pub fn to_json(&self) -> String {
+        let scatters: Vec<String> = self
+            .stats
+            .scatters
+            .iter()
+            .map(|(name, count)| format!("\"{}\": {}", name, count))
+            .collect();
+        return format!(
+            concat!(
+                "{{\n",
+                "  \"wall_time_secs\": {},\n",
+                "  \"camera_rays\": {},\n",
+                "  \"rays\": {},\n",
+                "  \"shadow_rays\": {},\n",
+                "  \"rays_per_second\": {},\n",
+                "  \"average_path_length\": {},\n",
+                "  \"intersection_tests\": {},\n",
+                "  \"intersection_tests_per_ray\": {},\n",
+                "  \"bvh_nodes_visited\": {},\n",
+                "  \"scatters\": {{{}}}\n",
+                "}}\n"
+            ),
+            self.wall_time.as_secs_f64(),
+            self.stats.camera_rays,
+            self.stats.rays,
+            self.stats.shadow_rays,
+            self.rays_per_second(),
+            self.average_path_length(),
+            self.stats.intersection_tests,
+            self.intersection_tests_per_ray(),
+            self.stats.bvh_nodes_visited,
+            scatters.join(", "),
+        );
+    }
+}
+
+impl Display for RenderReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Wall time:           {:.2}s", self.wall_time.as_secs_f64())?;
+        writeln!(f, "Camera rays:         {}", self.stats.camera_rays)?;
+        writeln!(f, "Rays traced:         {}", self.stats.rays)?;
+        writeln!(f, "Shadow rays:         {}", self.stats.shadow_rays)?;
+        writeln!(f, "Rays per second:     {:.0}", self.rays_per_second())?;
+        writeln!(f, "Average path length: {:.2}", self.average_path_length())?;
+        writeln!(f, "Tests per ray:       {:.2}", self.intersection_tests_per_ray())?;
+        writeln!(f, "BVH nodes visited:   {} (no BVH yet)", self.stats.bvh_nodes_visited)?;
+        write!(f, "Scatters:")?;
+        for (name, count) in self.stats.scatters.iter() {
+            write!(f, "\n  {:<18} {}", name, count)?;
+        }
+        return Ok(());
+    }
+}
+
+#[test]
+fn test_stats() {
+    let mut stats = Stats::new();
+    stats.camera_rays = 2;
+    stats.rays = 6;
+    stats.shadow_rays = 1;
+    stats.intersection_tests = 20;
+    stats.scatters.insert("diffuse", 3);
+    let mut other = stats.clone();
+    other.merge(&stats);
+    assert_eq!((other.rays, other.scatters["diffuse"]), (12, 6));
+
+    let report = RenderReport {
+        wall_time: Duration::from_secs(6),
+        stats,
+    };
+    assert_eq!(report.rays_per_second(), 1.0);
+    assert_eq!(report.average_path_length(), 2.5);
+    assert_eq!(report.intersection_tests_per_ray(), 20.0 / 6.0);
+    assert!(report.to_json().contains("\"bvh_nodes_visited\": 0,"));
+    assert!(report.to_json().contains("\"scatters\": {\"diffuse\": 3}"));
+}
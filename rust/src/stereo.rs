@@ -0,0 +1,161 @@
+use crate::colour::Colour;
+use crate::point::Point;
+use crate::vector::Vector;
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Convergence {
+    // Both eyes rotate inwards to look at the convergence point
+    ToeIn,
+    // Both eyes look straight ahead, with their frusta shifted to meet at the convergence distance
+    OffAxis,
+}
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum Stereo {
+    Mono,
+    Rig {
+        interocular: f64,
+        convergence: Convergence,
+        convergence_distance: f64,
+    },
+    // Omni-directional stereo, for use with an equirectangular projection
+    Omni {
+        interocular: f64,
+    },
+}
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum StereoLayout {
+    Separate,
+    SideBySide,
+    TopBottom,
+}
+
+// Where a single eye sits and looks
+pub struct EyeView {
+    pub look_from: Point,
+    pub look_at: Point,
+    // Horizontal lens shift, in scene units per unit of distance from the eye
+    pub shift: f64,
+    // Offset of an omni-directional eye from the centre of its viewing circle
+    pub ods_offset: f64,
+}
+
+impl Stereo {
+    // Returns the left then the right eye, or a single view for mono renders
+    pub fn views(&self, look_from: Point, look_at: Point, v_up: Vector) -> Vec<EyeView> {
+        let forward = (look_at - look_from).unit();
+        let right = forward.cross(v_up).unit();
+
+        let eye = |offset: f64| -> EyeView {
+            let mut view = EyeView {
+                look_from,
+                look_at,
+                shift: 0.0,
+                ods_offset: 0.0,
+            };
+            match *self {
+                Stereo::Mono => {}
+                Stereo::Rig {
+                    convergence,
+                    convergence_distance,
+                    ..
+                } => {
+                    view.look_from = look_from + right * offset;
+                    match convergence {
+                        Convergence::ToeIn => {
+                            view.look_at = look_from + forward * convergence_distance;
+                        }
+                        Convergence::OffAxis => {
+                            view.look_at = look_at + right * offset;
+                            view.shift = -offset / convergence_distance;
+                        }
+                    }
+                }
+                Stereo::Omni { .. } => view.ods_offset = offset,
+            }
+            return view;
+        };
+
+        return match *self {
+            Stereo::Mono => vec![eye(0.0)],
+            Stereo::Rig { interocular, .. } | Stereo::Omni { interocular } => {
+                vec![eye(-interocular / 2.0), eye(interocular / 2.0)]
+            }
+        };
+    }
+}
+
+// Packs a stereo pair into the requested layout, returning each output image's size and pixels
+pub fn pack(
+    layout: StereoLayout,
+    width: u32,
+    height: u32,
+    left: Vec<Colour>,
+    right: Vec<Colour>,
+) -> Vec<(u32, u32, Vec<Colour>)> {
+    return match layout {
+        StereoLayout::Separate => vec![(width, height, left), (width, height, right)],
+        StereoLayout::SideBySide => {
+            let mut pixels = Vec::with_capacity(left.len() * 2);
+            for (l, r) in left
+                .chunks(width as usize)
+                .zip(right.chunks(width as usize))
+            {
+                pixels.extend_from_slice(l);
+                pixels.extend_from_slice(r);
+            }
+            vec![(width * 2, height, pixels)]
+        }
+        StereoLayout::TopBottom => {
+            let mut pixels = left;
+            pixels.extend(right);
+            vec![(width, height * 2, pixels)]
+        }
+    };
+}
+
+#[test]
+fn test_stereo() {
+    let look_from = Point::new(0.0, 0.0, 0.0);
+    let look_at = Point::new(0.0, 0.0, -10.0);
+    let v_up = Vector::new(0.0, 1.0, 0.0);
+
+    let toe_in = Stereo::Rig {
+        interocular: 0.1,
+        convergence: Convergence::ToeIn,
+        convergence_distance: 2.0,
+    };
+    let views = toe_in.views(look_from, look_at, v_up);
+    assert_eq!(
+        (views[0].look_from.x(), views[1].look_from.x()),
+        (-0.05, 0.05)
+    );
+    assert_eq!((views[0].look_at.x(), views[0].look_at.z()), (0.0, -2.0));
+
+    let off_axis = Stereo::Rig {
+        interocular: 0.1,
+        convergence: Convergence::OffAxis,
+        convergence_distance: 2.0,
+    };
+    let views = off_axis.views(look_from, look_at, v_up);
+    assert_eq!(views[0].look_at.x(), -0.05);
+    assert_eq!((views[0].shift, views[1].shift), (0.025, -0.025));
+
+    let black = Colour::new(0.0, 0.0, 0.0);
+    let white = Colour::new(1.0, 1.0, 1.0);
+    let packed = pack(
+        StereoLayout::SideBySide,
+        2,
+        2,
+        vec![black; 4],
+        vec![white; 4],
+    );
+    let (width, height, pixels) = &packed[0];
+    assert_eq!((*width, *height), (4, 2));
+    let reds: Vec<f64> = pixels.iter().map(|p| p.r).collect();
+    assert_eq!(reds, vec![0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0]);
+}
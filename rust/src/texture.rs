@@ -0,0 +1,100 @@
+use std::io;
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::colour_space::{ColourSpace, Encoding};
+use crate::image::Image;
+use crate::point::Point;
+
+// A colour varying over a surface. Scalar parameters read a texture's red channel.
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: Point) -> Colour;
+}
+
+pub struct SolidColour {
+    pub colour: Colour,
+}
+
+impl SolidColour {
+    pub fn new(colour: Colour) -> Arc<SolidColour> {
+        return Arc::new(SolidColour { colour });
+    }
+
+    pub fn scalar(value: f64) -> Arc<SolidColour> {
+        return SolidColour::new(Colour::new(value, value, value));
+    }
+}
+
+impl Texture for SolidColour {
+    fn value(&self, _u: f64, _v: f64, _p: Point) -> Colour {
+        return self.colour;
+    }
+}
+
+// Alternates between two textures in a 3D grid of cubes
+pub struct Checker {
+    pub scale: f64,
+    pub even: Arc<dyn Texture>,
+    pub odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Arc<Checker> {
+        return Arc::new(Checker { scale, even, odd });
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, p: Point) -> Colour {
+        let cell = |x: f64| -> i64 { (x / self.scale).floor() as i64 };
+        if (cell(p.x()) + cell(p.y()) + cell(p.z())) % 2 == 0 {
+            return self.even.value(u, v, p);
+        }
+        return self.odd.value(u, v, p);
+    }
+}
+
+// An image stretched over the surface's (u, v) coordinates, with v running up the image.
+// Pixels are blended bilinearly, so heights read from it have slopes for bump mapping.
+pub struct ImageTexture {
+    pub image: Image,
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> Arc<ImageTexture> {
+        return Arc::new(ImageTexture { image });
+    }
+
+    // Loads the values as stored, for data such as normal, bump and opacity maps
+    pub fn load(path: &str) -> io::Result<Arc<ImageTexture>> {
+        return Ok(ImageTexture::new(Image::load(path)?));
+    }
+
+    // Loads a colour image, decoding it and bringing it into the working space
+    pub fn load_colour(
+        path: &str,
+        encoding: Encoding,
+        space: ColourSpace,
+        working: ColourSpace,
+    ) -> io::Result<Arc<ImageTexture>> {
+        let image = Image::load(path)?.into_working(encoding, space, working);
+        return Ok(ImageTexture::new(image));
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point) -> Colour {
+        let (width, height) = (self.image.width, self.image.height);
+        let x = u.rem_euclid(1.0) * width as f64 - 0.5;
+        let y = ((1.0 - v.clamp(0.0, 1.0)) * height as f64 - 0.5).max(0.0);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        // Wraps around in u, and stops at the edges in v
+        let x0 = (x0 as i64).rem_euclid(width as i64) as u32;
+        let x1 = (x0 + 1) % width;
+        let y0 = y0 as u32;
+        let top = self.image.get(x0, y0) * (1.0 - tx) + self.image.get(x1, y0) * tx;
+        let bottom = self.image.get(x0, y0 + 1) * (1.0 - tx) + self.image.get(x1, y0 + 1) * tx;
+        return top * (1.0 - ty) + bottom * ty;
+    }
+}
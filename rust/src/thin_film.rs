@@ -0,0 +1,207 @@
+use std::f64::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::colour::Colour;
+use crate::spectrum::{channel_at, integrate};
+
+// Complex numbers, for the phases and absorbing refractive indices of the film equations
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Complex {
+        return Complex { re, im };
+    }
+
+    fn norm_sq(self) -> f64 {
+        return self.re * self.re + self.im * self.im;
+    }
+
+    // Principal square root, which keeps evanescent waves decaying
+    fn sqrt(self) -> Complex {
+        let r = self.norm_sq().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        return Complex::new(re, if self.im < 0.0 { -im } else { im });
+    }
+
+    // e^(i z)
+    fn exp_i(self) -> Complex {
+        let scale = (-self.im).exp();
+        return Complex::new(scale * self.re.cos(), scale * self.re.sin());
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        return Complex::new(self.re + other.re, self.im + other.im);
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        return Complex::new(self.re - other.re, self.im - other.im);
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        return Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        );
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        let d = other.norm_sq();
+        return Complex::new(
+            (self.re * other.re + self.im * other.im) / d,
+            (self.im * other.re - self.re * other.im) / d,
+        );
+    }
+}
+
+// Cosine of the angle in a layer of index n, for light entering the stack from index n1
+fn cos_in_layer(sin2_1: f64, n1: f64, n: Complex) -> Complex {
+    let ratio = Complex::new(n1, 0.0) / n;
+    return (Complex::new(1.0, 0.0) - ratio * ratio * Complex::new(sin2_1, 0.0)).sqrt();
+}
+
+// Fresnel amplitude coefficients for s and p polarised light between two layers
+fn amplitudes(ni: Complex, cos_i: Complex, nj: Complex, cos_j: Complex) -> (Complex, Complex) {
+    let rs = (ni * cos_i - nj * cos_j) / (ni * cos_i + nj * cos_j);
+    let rp = (nj * cos_i - ni * cos_j) / (nj * cos_i + ni * cos_j);
+    return (rs, rp);
+}
+
+// A transparent layer about as thick as a wavelength of light, such as soap or oil, whose
+// reflections off its top and bottom interfere to give iridescent colours
+#[derive(Clone, Copy)]
+pub struct ThinFilm {
+    // In nanometres
+    pub thickness: f64,
+    pub refractive_idx: f64,
+}
+
+impl ThinFilm {
+    // Reflectance at one wavelength of a film on a base with complex refractive index
+    // eta + ik, seen from a medium of index outside_idx. Both reflections off the film are
+    // summed with their phase difference, along with all the ones bouncing inside it.
+    pub fn reflectance(
+        &self,
+        cos_theta: f64,
+        outside_idx: f64,
+        eta: f64,
+        k: f64,
+        wavelength: f64,
+    ) -> f64 {
+        let cos_1 = cos_theta.clamp(0.0, 1.0);
+        let sin2_1 = 1.0 - cos_1 * cos_1;
+        let n1 = Complex::new(outside_idx, 0.0);
+        let n2 = Complex::new(self.refractive_idx, 0.0);
+        let n3 = Complex::new(eta, k);
+        let cos_2 = cos_in_layer(sin2_1, outside_idx, n2);
+        let cos_3 = cos_in_layer(sin2_1, outside_idx, n3);
+
+        let (r12_s, r12_p) = amplitudes(n1, Complex::new(cos_1, 0.0), n2, cos_2);
+        let (r23_s, r23_p) = amplitudes(n2, cos_2, n3, cos_3);
+        // Phase gained by the round trip across the film
+        let phase =
+            (n2 * cos_2 * Complex::new(4.0 * PI * self.thickness / wavelength, 0.0)).exp_i();
+        let airy = |r12: Complex, r23: Complex| -> f64 {
+            let r = (r12 + r23 * phase) / (Complex::new(1.0, 0.0) + r12 * r23 * phase);
+            return r.norm_sq().min(1.0);
+        };
+        return 0.5 * (airy(r12_s, r23_s) + airy(r12_p, r23_p));
+    }
+
+    // Reflectance of the film on a base given per colour channel. On a path following a
+    // single wavelength it's the same in every channel, and otherwise it's the colour of the
+    // reflectance spectrum under white light.
+    pub fn reflectance_rgb(
+        &self,
+        cos_theta: f64,
+        outside_idx: f64,
+        eta: Colour,
+        k: Colour,
+        wavelength: Option<f64>,
+    ) -> Colour {
+        let at = |wavelength: f64| -> f64 {
+            return self.reflectance(
+                cos_theta,
+                outside_idx,
+                channel_at(eta, wavelength),
+                channel_at(k, wavelength),
+                wavelength,
+            );
+        };
+        return match wavelength {
+            Some(wavelength) => {
+                let r = at(wavelength);
+                Colour::new(r, r, r)
+            }
+            None => integrate(at),
+        };
+    }
+}
+
+#[test]
+fn test_thin_film() {
+    use crate::microfacet::{fresnel_conductor, fresnel_dielectric};
+
+    // A film with no thickness, or matching the base, makes no difference
+    let none = ThinFilm {
+        thickness: 0.0,
+        refractive_idx: 1.33,
+    };
+    let matched = ThinFilm {
+        thickness: 300.0,
+        refractive_idx: 1.5,
+    };
+    for cos_theta in [1.0, 0.7, 0.2] {
+        let bare = fresnel_dielectric(cos_theta, 1.5);
+        assert!((none.reflectance(cos_theta, 1.0, 1.5, 0.0, 550.0) - bare).abs() < 1e-9);
+        assert!((matched.reflectance(cos_theta, 1.0, 1.5, 0.0, 550.0) - bare).abs() < 1e-9);
+    }
+    let gold = (
+        Colour::new(0.143, 0.374, 1.442),
+        Colour::new(3.983, 2.385, 1.603),
+    );
+    let bare = fresnel_conductor(0.6, gold.0, gold.1);
+    assert!((none.reflectance(0.6, 1.0, gold.0.r, gold.1.r, 700.0) - bare.r).abs() < 1e-9);
+
+    // A quarter wave film cancels reflections at its wavelength, and a half wave one doesn't
+    let quarter = ThinFilm {
+        thickness: 550.0 / (4.0 * 1.22),
+        refractive_idx: 1.22,
+    };
+    assert!(quarter.reflectance(1.0, 1.0, 1.5, 0.0, 550.0) < 0.001);
+    assert!(quarter.reflectance(1.0, 1.0, 1.5, 0.0, 275.0) > 0.03);
+
+    // Soap films are coloured, while staying within the bounds of energy conservation
+    let soap = ThinFilm {
+        thickness: 400.0,
+        refractive_idx: 1.33,
+    };
+    let c = soap.reflectance_rgb(
+        0.8,
+        1.0,
+        Colour::new(1.0, 1.0, 1.0),
+        Colour::new(0.0, 0.0, 0.0),
+        None,
+    );
+    assert!((c.r - c.g).abs() > 0.01 || (c.g - c.b).abs() > 0.01);
+    assert!((0..50).all(|i| {
+        let r = soap.reflectance(i as f64 / 49.0, 1.0, 1.5, 0.0, 400.0 + i as f64 * 7.0);
+        (0.0..=1.0).contains(&r)
+    }));
+}
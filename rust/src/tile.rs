@@ -0,0 +1,179 @@
+use indicatif::ProgressBar;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::film::{Film, FilmTile};
+
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum TileOrder {
+    Scanline,
+    Spiral,
+    Hilbert,
+}
+
+// A rectangle of pixels in image space, where (0, 0) is the top left pixel
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> {
+        let tile = *self;
+        return (tile.y..tile.y + tile.height)
+            .flat_map(move |y| (tile.x..tile.x + tile.width).map(move |x| (x, y)));
+    }
+}
+
+pub fn generate_tiles(
+    image_width: u32,
+    image_height: u32,
+    tile_size: u32,
+    order: TileOrder,
+) -> Vec<Tile> {
+    assert!(tile_size > 0, "Tile size must be at least one pixel.");
+    if image_width == 0 || image_height == 0 {
+        return Vec::new();
+    }
+    let nx = image_width.div_ceil(tile_size);
+    let ny = image_height.div_ceil(tile_size);
+
+    let coords = match order {
+        TileOrder::Scanline => scanline_order(nx, ny),
+        TileOrder::Spiral => spiral_order(nx, ny),
+        TileOrder::Hilbert => hilbert_order(nx, ny),
+    };
+
+    return coords
+        .into_iter()
+        .map(|(tx, ty)| {
+            let x = tx * tile_size;
+            let y = ty * tile_size;
+            Tile {
+                x,
+                y,
+                width: tile_size.min(image_width - x),
+                height: tile_size.min(image_height - y),
+            }
+        })
+        .collect();
+}
+
+fn scanline_order(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    return (0..ny).flat_map(|ty| (0..nx).map(move |tx| (tx, ty))).collect();
+}
+
+fn spiral_order(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    // Walks outwards from the centre tile, skipping positions outside the grid
+    let total = (nx * ny) as usize;
+    let mut out = Vec::with_capacity(total);
+    let (mut x, mut y) = (((nx - 1) / 2) as i64, ((ny - 1) / 2) as i64);
+    let (mut dx, mut dy) = (1, 0);
+    let mut leg_length = 1;
+
+    while out.len() < total {
+        for _ in 0..2 {
+            for _ in 0..leg_length {
+                if x >= 0 && y >= 0 && x < nx as i64 && y < ny as i64 {
+                    out.push((x as u32, y as u32));
+                }
+                x += dx;
+                y += dy;
+            }
+            (dx, dy) = (-dy, dx);
+        }
+        leg_length += 1;
+    }
+    out.truncate(total);
+    return out;
+}
+
+fn hilbert_order(nx: u32, ny: u32) -> Vec<(u32, u32)> {
+    let n = nx.max(ny).next_power_of_two();
+    let mut out = scanline_order(nx, ny);
+    out.sort_by_key(|&(x, y)| hilbert_index(n, x, y));
+    return out;
+}
+
+fn hilbert_index(n: u32, x: u32, y: u32) -> u64 {
+    // Distance along the Hilbert curve filling an n x n grid
+    let (mut x, mut y) = (x, y);
+    let mut d: u64 = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        d += (s as u64) * (s as u64) * ((3 * rx) ^ ry) as u64;
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            (x, y) = (y, x);
+        }
+        s /= 2;
+    }
+    return d;
+}
+
+pub fn render_tiles<F>(
+    tiles: &[Tile],
+    threads: usize,
+    film: &mut Film,
+    bar: &ProgressBar,
+    render_tile: F,
+) where
+    F: Fn(&Tile, &mut FilmTile) + Sync,
+{
+    // Pass 0 threads to use one per logical core
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("Failed to build thread pool.");
+    let film = Mutex::new(film);
+
+    // Workers pull tiles from a shared counter so that the requested order is honoured
+    let next = AtomicUsize::new(0);
+    pool.scope(|s| {
+        for _ in 0..pool.current_num_threads() {
+            s.spawn(|_| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                if idx >= tiles.len() {
+                    break;
+                }
+                let tile = &tiles[idx];
+                let mut film_tile = film.lock().unwrap().tile(tile);
+                render_tile(tile, &mut film_tile);
+                film.lock().unwrap().merge(film_tile);
+                bar.inc(1);
+            });
+        }
+    });
+}
+
+#[test]
+fn test_tiles() {
+    for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+        let tiles = generate_tiles(37, 21, 8, order);
+        assert_eq!(tiles.len(), 5 * 3);
+
+        let mut covered = vec![0; 37 * 21];
+        for tile in tiles.iter() {
+            for (x, y) in tile.pixels() {
+                covered[(y * 37 + x) as usize] += 1;
+            }
+        }
+        assert!(covered.iter().all(|&c| c == 1));
+    }
+
+    let spiral = generate_tiles(48, 48, 16, TileOrder::Spiral);
+    assert_eq!((spiral[0].x, spiral[0].y), (16, 16));
+    let hilbert = generate_tiles(32, 32, 16, TileOrder::Hilbert);
+    let starts: Vec<(u32, u32)> = hilbert.iter().map(|t| (t.x, t.y)).collect();
+    assert_eq!(starts, vec![(0, 0), (0, 16), (16, 16), (16, 0)]);
+    assert!(generate_tiles(0, 21, 8, TileOrder::Spiral).is_empty());
+}
@@ -0,0 +1,129 @@
+use crate::animation::{Interpolation, Keyframe, Track};
+use crate::hittable::{Hit, HitRecord, Interval};
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::utils::deg_to_rad;
+use crate::vector::Vector;
+
+// A uniform scale, then rotation about the x, y and z axes in degrees, then a translation,
+// each applied about the world origin
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub translation: Vector,
+    pub rotation: Vector,
+    pub scale: f64,
+}
+
+impl Transform {
+    fn rotation_matrix(&self) -> [Vector; 3] {
+        let (sx, cx) = deg_to_rad(self.rotation.x()).sin_cos();
+        let (sy, cy) = deg_to_rad(self.rotation.y()).sin_cos();
+        let (sz, cz) = deg_to_rad(self.rotation.z()).sin_cos();
+        // Rows of Rz * Ry * Rx
+        return [
+            Vector::new(cz * cy, cz * sy * sx - sz * cx, cz * sy * cx + sz * sx),
+            Vector::new(sz * cy, sz * sy * sx + cz * cx, sz * sy * cx - cz * sx),
+            Vector::new(-sy, cy * sx, cy * cx),
+        ];
+    }
+}
+
+pub struct TransformTrack {
+    pub translation: Track<Vector>,
+    pub rotation: Track<Vector>,
+    pub scale: Track<f64>,
+}
+
+impl TransformTrack {
+    pub fn translation(
+        keys: Vec<Keyframe<Vector>>,
+        interpolation: Interpolation,
+    ) -> TransformTrack {
+        return TransformTrack {
+            translation: Track::new(keys, interpolation),
+            rotation: Track::new(
+                vec![Keyframe::new(0.0, Vector::new(0.0, 0.0, 0.0))],
+                Interpolation::Linear,
+            ),
+            scale: Track::new(vec![Keyframe::new(0.0, 1.0)], Interpolation::Linear),
+        };
+    }
+
+    pub fn evaluate(&self, time: f64) -> Transform {
+        return Transform {
+            translation: self.translation.evaluate(time),
+            rotation: self.rotation.evaluate(time),
+            scale: self.scale.evaluate(time),
+        };
+    }
+}
+
+pub struct Transformed {
+    object: Box<dyn Hit>,
+    translation: Vector,
+    scale: f64,
+    // Rows of the rotation matrix, whose transpose is its inverse
+    rotation: [Vector; 3],
+}
+
+impl Transformed {
+    pub fn new(object: impl Hit + 'static, transform: Transform) -> Transformed {
+        return Transformed {
+            object: Box::new(object),
+            translation: transform.translation,
+            scale: transform.scale,
+            rotation: transform.rotation_matrix(),
+        };
+    }
+
+    fn rotate(&self, v: Vector) -> Vector {
+        return Vector::new(
+            self.rotation[0].dot(v),
+            self.rotation[1].dot(v),
+            self.rotation[2].dot(v),
+        );
+    }
+
+    fn unrotate(&self, v: Vector) -> Vector {
+        return (self.rotation[0] * v.x())
+            + (self.rotation[1] * v.y())
+            + (self.rotation[2] * v.z());
+    }
+}
+
+impl Transformed {
+    // The ray in object space, where it keeps the same parametrisation
+    fn to_local(&self, ray: &Ray) -> Ray {
+        let origin = self.unrotate((ray.origin - self.translation).v) / self.scale;
+        let direction = self.unrotate(ray.direction) / self.scale;
+        return Ray::new(Point::from(origin), direction);
+    }
+
+    fn to_world(&self, ray: &Ray, mut rec: HitRecord) -> HitRecord {
+        rec.p = ray.at(rec.t);
+        rec.normal = self.rotate(rec.normal);
+        rec.geometric_normal = self.rotate(rec.geometric_normal);
+        rec.dpdu = self.rotate(rec.dpdu) * self.scale;
+        rec.dpdv = self.rotate(rec.dpdv) * self.scale;
+        return rec;
+    }
+}
+
+impl Hit for Transformed {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let rec = self.object.hit(&self.to_local(ray), t_min, t_max)?;
+        return Some(self.to_world(ray, rec));
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        return self
+            .object
+            .intervals(&self.to_local(ray))
+            .into_iter()
+            .map(|interval| Interval {
+                enter: self.to_world(ray, interval.enter),
+                exit: self.to_world(ray, interval.exit),
+            })
+            .collect();
+    }
+}
@@ -8,6 +8,10 @@ pub fn write_file(path: &String, content: &String) -> std::io::Result<()> {
     return Ok(());
 }
 
-pub fn deg_to_rad(deg: i32) -> f64 {
-    return (deg as f64 * PI) / 180.0;
+pub fn deg_to_rad(deg: f64) -> f64 {
+    return (deg * PI) / 180.0;
+}
+
+pub fn rad_to_deg(rad: f64) -> f64 {
+    return (rad * 180.0) / PI;
 }
@@ -161,6 +161,48 @@ impl Display for Vector {
     }
 }
 
+// Orthonormal basis around a unit normal, which becomes the local z axis
+#[derive(Clone, Copy)]
+pub struct Onb {
+    pub u: Vector,
+    pub v: Vector,
+    pub w: Vector,
+}
+
+impl Onb {
+    // Duff et al.'s branchless construction, which is continuous everywhere but the -z pole
+    pub fn from_w(w: Vector) -> Onb {
+        let sign = 1.0_f64.copysign(w.z());
+        let a = -1.0 / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        let u = Vector::new(1.0 + sign * w.x() * w.x() * a, sign * b, -sign * w.x());
+        let v = Vector::new(b, sign + w.y() * w.y() * a, -w.y());
+        return Onb { u, v, w };
+    }
+
+    // Keeps the tangent as close to the given direction as the normal allows
+    pub fn from_tangent(w: Vector, tangent: Vector) -> Onb {
+        let u = tangent - w * tangent.dot(w);
+        if u.near_zero() {
+            return Onb::from_w(w);
+        }
+        let u = u.unit();
+        return Onb {
+            u,
+            v: w.cross(u),
+            w,
+        };
+    }
+
+    pub fn to_local(self, d: Vector) -> Vector {
+        return Vector::new(d.dot(self.u), d.dot(self.v), d.dot(self.w));
+    }
+
+    pub fn to_world(self, d: Vector) -> Vector {
+        return self.u * d.x() + self.v * d.y() + self.w * d.z();
+    }
+}
+
 pub fn random_in_unit_sphere() -> Vector {
     loop {
         let v = Vector::random(-1.0..1.0);
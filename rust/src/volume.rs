@@ -0,0 +1,385 @@
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+use crate::colour::Colour;
+use crate::hittable::{Hit, HitRecord, Interval};
+use crate::material::Material;
+use crate::medium::Medium;
+use crate::point::Point;
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+// How thick a medium is at each point, such as the water in a cloud or the soot in smoke
+pub trait Density: Send + Sync {
+    // At a point in world space
+    fn density(&self, p: Point) -> f64;
+
+    // No less than the density anywhere, which light is tracked through the medium against
+    fn max_density(&self) -> f64;
+}
+
+// The same density everywhere
+impl Density for f64 {
+    fn density(&self, _p: Point) -> f64 {
+        return *self;
+    }
+
+    fn max_density(&self) -> f64 {
+        return *self;
+    }
+}
+
+// Densities sampled at the centres of the cells of a regular grid filling a box, such as the
+// output of a smoke simulation. They're interpolated between, and are zero outside the box.
+pub struct Grid {
+    min: Point,
+    max: Point,
+    size: [usize; 3],
+    // x varies fastest, then y, then z
+    values: Vec<f64>,
+    max_value: f64,
+}
+
+impl Grid {
+    pub fn new(min: Point, max: Point, size: [usize; 3], values: Vec<f64>) -> Arc<Grid> {
+        assert_eq!(values.len(), size[0] * size[1] * size[2]);
+        let max_value = values.iter().fold(0.0_f64, |a, &b| a.max(b));
+        return Arc::new(Grid {
+            min,
+            max,
+            size,
+            values,
+            max_value,
+        });
+    }
+
+    // Reads a raw file of little endian 32 bit floats, with x varying fastest, then y, then z
+    pub fn load(path: &str, size: [usize; 3], min: Point, max: Point) -> io::Result<Arc<Grid>> {
+        let bytes = fs::read(path)?;
+        if bytes.len() != 4 * size[0] * size[1] * size[2] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Grid file doesn't match its size.",
+            ));
+        }
+        let values = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64)
+            .collect();
+        return Ok(Grid::new(min, max, size, values));
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> f64 {
+        return self.values[(z * self.size[1] + y) * self.size[0] + x];
+    }
+}
+
+impl Density for Grid {
+    fn density(&self, p: Point) -> f64 {
+        // Position in cells, and the lower corner of the samples around it on each axis
+        let mut corner = [0; 3];
+        let mut t = [0.0; 3];
+        for axis in 0..3 {
+            let extent = self.max[axis] - self.min[axis];
+            let x = (p[axis] - self.min[axis]) / extent;
+            if !(0.0..=1.0).contains(&x) {
+                return 0.0;
+            }
+            let n = self.size[axis];
+            let cell = (x * n as f64 - 0.5).clamp(0.0, (n - 1) as f64);
+            corner[axis] = (cell as usize).min(n.saturating_sub(2));
+            t[axis] = if n > 1 {
+                cell - corner[axis] as f64
+            } else {
+                0.0
+            };
+        }
+        let next = |axis: usize| -> usize { (corner[axis] + 1).min(self.size[axis] - 1) };
+        let mut value = 0.0;
+        for (z, wz) in [(corner[2], 1.0 - t[2]), (next(2), t[2])] {
+            for (y, wy) in [(corner[1], 1.0 - t[1]), (next(1), t[1])] {
+                for (x, wx) in [(corner[0], 1.0 - t[0]), (next(0), t[0])] {
+                    value += self.value(x, y, z) * wx * wy * wz;
+                }
+            }
+        }
+        return value;
+    }
+
+    fn max_density(&self) -> f64 {
+        return self.max_value;
+    }
+}
+
+// Fractal Perlin noise, for procedural clouds and smoke. Below the threshold it's clear, which
+// breaks it into wisps and billows, and it thins out towards the edge of a sphere so the
+// volume has no hard boundary.
+pub struct Noise {
+    pub centre: Point,
+    pub radius: f64,
+    // Of the largest features, per unit distance
+    pub frequency: f64,
+    pub octaves: u32,
+    pub threshold: f64,
+    pub seed: u32,
+}
+
+impl Noise {
+    pub fn new(centre: Point, radius: f64, frequency: f64, octaves: u32, seed: u32) -> Arc<Noise> {
+        return Arc::new(Noise {
+            centre,
+            radius,
+            frequency,
+            octaves,
+            threshold: 0.3,
+            seed,
+        });
+    }
+
+    // Sum of octaves of noise, each at twice the frequency and half the amplitude of the last,
+    // scaled to between zero and one
+    fn fractal(&self, p: Point) -> f64 {
+        let (mut sum, mut amplitude, mut total) = (0.0, 1.0, 0.0);
+        let mut frequency = self.frequency;
+        for octave in 0..self.octaves {
+            let q = [p.x() * frequency, p.y() * frequency, p.z() * frequency];
+            sum += amplitude * perlin(q, self.seed.wrapping_add(octave));
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+        return (0.5 + 0.5 * sum / total.max(1e-9)).clamp(0.0, 1.0);
+    }
+}
+
+impl Density for Noise {
+    fn density(&self, p: Point) -> f64 {
+        let r = (p - self.centre).length() / self.radius;
+        if r >= 1.0 {
+            return 0.0;
+        }
+        let t = ((r - 0.5) / 0.5).clamp(0.0, 1.0);
+        let falloff = 1.0 - t * t * (3.0 - 2.0 * t);
+        let value = self.fractal(p) * falloff;
+        return ((value - self.threshold) / (1.0 - self.threshold)).max(0.0);
+    }
+
+    fn max_density(&self) -> f64 {
+        return 1.0;
+    }
+}
+
+// Mixes the lattice coordinates and seed into a well scrambled integer
+fn hash(x: i64, y: i64, z: i64, seed: u32) -> u64 {
+    let mut h = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9)
+        ^ (seed as u64).wrapping_mul(0x27D4_EB2F_1656_67C5);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    h ^= h >> 33;
+    return h;
+}
+
+// Ken Perlin's improved gradient noise, between about -1 and 1
+fn perlin(p: [f64; 3], seed: u32) -> f64 {
+    // Gradients towards the edges of a cube
+    const GRADIENTS: [[f64; 3]; 12] = [
+        [1.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0],
+        [1.0, -1.0, 0.0],
+        [-1.0, -1.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [-1.0, 0.0, 1.0],
+        [1.0, 0.0, -1.0],
+        [-1.0, 0.0, -1.0],
+        [0.0, 1.0, 1.0],
+        [0.0, -1.0, 1.0],
+        [0.0, 1.0, -1.0],
+        [0.0, -1.0, -1.0],
+    ];
+    let fade = |t: f64| -> f64 { t * t * t * (t * (t * 6.0 - 15.0) + 10.0) };
+    let cell = p.map(|v| v.floor());
+    let f = [p[0] - cell[0], p[1] - cell[1], p[2] - cell[2]];
+    let (i, j, k) = (cell[0] as i64, cell[1] as i64, cell[2] as i64);
+    let corner = |dx: i64, dy: i64, dz: i64| -> f64 {
+        let g = GRADIENTS[(hash(i + dx, j + dy, k + dz, seed) % 12) as usize];
+        let d = [f[0] - dx as f64, f[1] - dy as f64, f[2] - dz as f64];
+        return g[0] * d[0] + g[1] * d[1] + g[2] * d[2];
+    };
+    let lerp = |a: f64, b: f64, t: f64| -> f64 { a + (b - a) * t };
+    let (u, v, w) = (fade(f[0]), fade(f[1]), fade(f[2]));
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u);
+    return lerp(lerp(x00, x10, v), lerp(x01, x11, v), w);
+}
+
+// The surface of a volume, which light passes straight through. Crossing it enters or leaves
+// the volume's medium.
+struct Boundary {
+    medium: Medium,
+}
+
+impl Material for Boundary {
+    fn name(&self) -> &'static str {
+        return "volume";
+    }
+
+    fn scatter(&self, ray: &Ray, record: &HitRecord) -> Option<(Ray, Colour)> {
+        return Some((
+            Ray::new(record.p, ray.direction),
+            Colour::new(1.0, 1.0, 1.0),
+        ));
+    }
+
+    fn medium(&self) -> Option<Medium> {
+        return Some(self.medium.clone());
+    }
+
+    fn invisible(&self) -> bool {
+        return true;
+    }
+}
+
+// A box filled with a medium, such as fog, or with a density, a cloud or a fire. Its density is
+// looked up in world space, so the volume is placed by its bounds rather than transformed.
+pub struct Volume {
+    pub min: Point,
+    pub max: Point,
+    boundary: Arc<dyn Material>,
+}
+
+impl Volume {
+    pub fn new(min: Point, max: Point, medium: Medium) -> Volume {
+        // Light is only sampled inside media with a density, so homogeneous ones, like fog,
+        // are given a constant one
+        let density = medium.density.clone().unwrap_or_else(|| Arc::new(1.0));
+        let medium = Medium {
+            density: Some(density),
+            ..medium
+        };
+        return Volume {
+            min,
+            max,
+            boundary: Arc::new(Boundary { medium }),
+        };
+    }
+}
+
+impl Volume {
+    // Where the ray's line enters and leaves the box, with the axes of the faces it enters and
+    // leaves by
+    fn slabs(&self, ray: &Ray) -> Option<((f64, usize), (f64, usize))> {
+        // Where the ray enters and leaves the slab between each pair of faces
+        let (mut near, mut far) = (f64::NEG_INFINITY, f64::INFINITY);
+        let (mut near_axis, mut far_axis) = (0, 0);
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > near {
+                (near, near_axis) = (t0, axis);
+            }
+            if t1 < far {
+                (far, far_axis) = (t1, axis);
+            }
+        }
+        if near > far {
+            return None;
+        }
+        return Some(((near, near_axis), (far, far_axis)));
+    }
+
+    fn record(&self, ray: &Ray, t: f64, axis: usize) -> HitRecord {
+        let p = ray.at(t);
+        // Outwards from whichever face is closest to the point
+        let mut normal = Vector::new(0.0, 0.0, 0.0);
+        let centre = 0.5 * (self.min[axis] + self.max[axis]);
+        normal[axis] = if p[axis] > centre { 1.0 } else { -1.0 };
+        return HitRecord::new_from_ray(p, normal, t, ray, self.boundary.clone());
+    }
+}
+
+impl Hit for Volume {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let ((near, near_axis), (far, far_axis)) = self.slabs(ray)?;
+        let (t, axis) = if near >= t_min && near <= t_max {
+            (near, near_axis)
+        } else if far >= t_min && far <= t_max {
+            (far, far_axis)
+        } else {
+            return None;
+        };
+        return Some(self.record(ray, t, axis));
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        return match self.slabs(ray) {
+            Some(((near, near_axis), (far, far_axis))) => vec![Interval {
+                enter: self.record(ray, near, near_axis),
+                exit: self.record(ray, far, far_axis),
+            }],
+            None => Vec::new(),
+        };
+    }
+}
+
+#[test]
+fn test_volume() {
+    // Samples are at the cells' centres, and interpolated linearly between them
+    let grid = Grid::new(
+        Point::new(0.0, 0.0, 0.0),
+        Point::new(2.0, 1.0, 1.0),
+        [2, 1, 1],
+        vec![0.0, 4.0],
+    );
+    assert_eq!(grid.max_density(), 4.0);
+    assert!((grid.density(Point::new(0.5, 0.5, 0.5)) - 0.0).abs() < 1e-12);
+    assert!((grid.density(Point::new(1.0, 0.2, 0.9)) - 2.0).abs() < 1e-12);
+    assert!((grid.density(Point::new(1.9, 0.5, 0.5)) - 4.0).abs() < 1e-12);
+    assert_eq!(grid.density(Point::new(2.1, 0.5, 0.5)), 0.0);
+
+    // Noise stays within its bounds and sphere, and isn't empty or solid
+    let noise = Noise::new(Point::new(0.0, 0.0, 0.0), 1.0, 2.0, 4, 7);
+    let values: Vec<f64> = (0..1000)
+        .map(|i| {
+            let x = i as f64 / 1000.0;
+            noise.density(Point::new(
+                x - 0.5,
+                (x * 7.0).sin() * 0.5,
+                (x * 13.0).cos() * 0.5,
+            ))
+        })
+        .collect();
+    assert!(values
+        .iter()
+        .all(|v| (0.0..=noise.max_density()).contains(v)));
+    assert!(values.contains(&0.0) && values.iter().any(|&v| v > 0.1));
+    assert_eq!(noise.density(Point::new(0.0, 1.01, 0.0)), 0.0);
+
+    // Rays enter the box through its near face and leave through its far one
+    let volume = Volume::new(
+        Point::new(-1.0, -1.0, -1.0),
+        Point::new(1.0, 1.0, 1.0),
+        Medium::new(Colour::new(1.0, 1.0, 1.0)),
+    );
+    let ray = Ray::new(Point::new(0.5, 0.2, 5.0), Vector::new(0.0, 0.0, -2.0));
+    let entry = volume.hit(&ray, 0.001, f64::INFINITY).unwrap();
+    assert!((entry.t - 2.0).abs() < 1e-12 && entry.front_face);
+    assert!(entry.material.invisible() && entry.normal.z() == 1.0);
+    let exit = volume.hit(&ray, entry.t + 0.001, f64::INFINITY).unwrap();
+    assert!((exit.t - 3.0).abs() < 1e-12 && !exit.front_face);
+    assert!(volume
+        .hit(
+            &Ray::new(Point::new(3.0, 0.0, 5.0), ray.direction),
+            0.001,
+            f64::INFINITY
+        )
+        .is_none());
+}